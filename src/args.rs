@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use bevy::prelude::Resource;
 use clap::{Parser, Subcommand};
 
@@ -8,19 +10,53 @@ use clap::{Parser, Subcommand};
 /// Run a test level
 /// ./snake-bird -t 0
 /// ./snake-bird --test_level 0
+/// Run a level file from anywhere on disk
+/// ./snake-bird --level-file ~/levels/puzzle.lvl
 /// // Run the automated tests
 /// ./snake-bird test
 /// // Run the automated tests for a specific test case
 /// ./snake-bird -t 0 test
+/// // Play the levels of another pack of the packs folder
+/// ./snake-bird --pack bonus -l 0
+/// // Play with two players on the same keyboard
+/// ./snake-bird --coop
+/// // Use snake colors that stay distinct with color blindness
+/// ./snake-bird --color-blind
+/// // Upgrade the level files to the latest version
+/// ./snake-bird migrate
+/// // Build a level from a 2D puzzle
+/// ./snake-bird import puzzle.txt assets/levels/puzzle.lvl
+/// // Convert level geometry from and to MagicaVoxel
+/// ./snake-bird import-vox blocks.vox assets/levels/blocks.lvl
+/// ./snake-bird export-vox assets/levels/level1.lvl
+/// // Draw a level preview with the cells of its solution, no graphics needed
+/// ./snake-bird svg assets/levels/level1.lvl --solution "0,1,0 1,1,0 2,1,0"
 
 #[derive(Parser, Debug, Default, Clone, Resource)]
 pub struct Args {
+    /// Index of the level in the pack.
     #[arg(short, long)]
     pub level: Option<usize>,
 
+    /// Name of the level pack, from the packs folder.
+    #[arg(long)]
+    pub pack: Option<String>,
+
     #[arg(short, long)]
     pub test_level: Option<String>,
 
+    /// Path of a level file, it doesn't need to be in the assets folder.
+    #[arg(long)]
+    pub level_file: Option<PathBuf>,
+
+    /// Player two controls the second snake with the arrow keys.
+    #[arg(long)]
+    pub coop: bool,
+
+    /// Snakes use a palette told apart with color vision deficiencies.
+    #[arg(long)]
+    pub color_blind: bool,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -33,4 +69,43 @@ pub enum Commands {
         test_case: Option<usize>,
     },
     Editor,
+    /// Rewrite the level files to the latest version in place.
+    Migrate {
+        #[arg(default_value = "assets/levels")]
+        path: PathBuf,
+    },
+    /// Build a planar level from a 2D side view puzzle.
+    Import {
+        source: PathBuf,
+        /// Defaults to the source with the level extension.
+        destination: Option<PathBuf>,
+    },
+    /// Build a level from the voxels of a MagicaVoxel file.
+    ImportVox {
+        source: PathBuf,
+        /// Defaults to the source with the level extension.
+        destination: Option<PathBuf>,
+        /// The entity of each palette index, defaults to assets/vox.palette.
+        #[arg(long)]
+        palette: Option<PathBuf>,
+    },
+    /// Write the entities of a level as a MagicaVoxel file.
+    ExportVox {
+        source: PathBuf,
+        /// Defaults to the source with the vox extension.
+        destination: Option<PathBuf>,
+        /// The entity of each palette index, defaults to assets/vox.palette.
+        #[arg(long)]
+        palette: Option<PathBuf>,
+    },
+    /// Draw an isometric SVG preview of a level.
+    Svg {
+        source: PathBuf,
+        /// Defaults to the source with the svg extension.
+        destination: Option<PathBuf>,
+        /// Cells visited by a snake head, drawn as a path: x,y,z separated by spaces.
+        /// Drawn as written, gravity, falls and pushes are not played.
+        #[arg(long)]
+        solution: Option<String>,
+    },
 }
@@ -1,12 +1,91 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::{error::Error, process};
+
 use bevy::prelude::App;
-use cat_snake::args::*;
+use cat_snake::{
+    args::*,
+    level::{
+        migrations::migrate_levels_in,
+        snakebird_import::import_snakebird_file,
+        svg_preview::{export_svg_file, SVG_EXTENSION},
+        vox::{export_vox_file, import_vox_file, VOX_EXTENSION},
+    },
+};
 use clap::Parser;
 
 fn main() {
     let args = Args::parse();
-    let mut app = App::new();
 
-    cat_snake::run(&mut app, &args);
+    let result = match &args.command {
+        Some(Commands::Migrate { path }) => migrate_levels_in(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|migrated| {
+                if migrated.skipped > 0 {
+                    Err(format!(
+                        "{} level(s) migrated, {} skipped.",
+                        migrated.upgraded, migrated.skipped
+                    )
+                    .into())
+                } else {
+                    Ok(format!("{} level(s) migrated.", migrated.upgraded))
+                }
+            }),
+        Some(Commands::Import {
+            source,
+            destination,
+        }) => {
+            let destination = destination
+                .clone()
+                .unwrap_or_else(|| source.with_extension("lvl"));
+            import_snakebird_file(source, &destination)
+                .map(|()| format!("Imported {}", destination.display()))
+        }
+        Some(Commands::ImportVox {
+            source,
+            destination,
+            palette,
+        }) => {
+            let destination = destination
+                .clone()
+                .unwrap_or_else(|| source.with_extension("lvl"));
+            import_vox_file(source, &destination, palette.as_deref())
+                .map(|()| format!("Imported {}", destination.display()))
+        }
+        Some(Commands::ExportVox {
+            source,
+            destination,
+            palette,
+        }) => {
+            let destination = destination
+                .clone()
+                .unwrap_or_else(|| source.with_extension(VOX_EXTENSION));
+            export_vox_file(source, &destination, palette.as_deref())
+                .map(|()| format!("Exported {}", destination.display()))
+        }
+        Some(Commands::Svg {
+            source,
+            destination,
+            solution,
+        }) => {
+            let destination = destination
+                .clone()
+                .unwrap_or_else(|| source.with_extension(SVG_EXTENSION));
+            export_svg_file(source, &destination, solution.as_deref())
+                .map(|()| format!("Exported {}", destination.display()))
+        }
+        Some(Commands::Test { .. } | Commands::Editor) | None => {
+            let mut app = App::new();
+            cat_snake::run(&mut app, &args);
+            return;
+        }
+    };
+
+    match result {
+        Ok(message) => println!("{}", message),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(1);
+        }
+    }
 }
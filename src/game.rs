@@ -5,18 +5,27 @@ use bevy_tweening::TweeningPlugin;
 use gameplay::camera_plugin::CameraPlugin;
 use gameplay::game_constants_plugin::*;
 use gameplay::level_entities::LevelEntity;
+use gameplay::level_info_plugin::LevelInfoPlugin;
 use gameplay::level_plugin::{
-    ClearLevelEvent, CurrentLevelMetadata, LevelPlugin, StartLevelEventWithIndex,
+    ClearLevelEvent, CurrentLevelMetadata, LevelFile, LevelPlugin, StartLevelEventWithIndex,
     StartLevelEventWithLevelAssetPath,
 };
 use gameplay::movement_plugin::MovementPlugin;
+use gameplay::npc_plugin::NpcPlugin;
+use gameplay::score_plugin::ScorePlugin;
+use gameplay::snake_body::SnakeBodyPlugin;
+use gameplay::snake_head::SnakeHeadPlugin;
 use gameplay::snake_plugin::SnakePlugin;
+use gameplay::theme_plugin::ThemePlugin;
+use gameplay::wall_groups::WallGroupPlugin;
 use iyes_loopless::prelude::IntoConditionalSystem;
 use iyes_loopless::{
     prelude::{AppLooplessStateExt, ConditionSet},
     state::NextState,
 };
+use level::level_pack::ActivePack;
 use library::LibraryPlugin;
+use menus::level_error_menu::LevelErrorMenuPlugin;
 use menus::main_menu::MainMenuPlugin;
 use menus::select_level_menu::{NextLevel, SelectLevelMenuPlugin};
 use menus::MenuPlugin;
@@ -35,12 +44,15 @@ pub mod utils;
 #[cfg(target_arch = "wasm32")]
 mod web_main;
 
+pub const WINDOW_TITLE: &str = "CatSnake";
+
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
     MainMenu,
     SelectLevelMenu,
     Game,
     Editor,
+    LevelError,
 }
 
 pub struct GamePlugin {
@@ -58,7 +70,14 @@ impl Plugin for GamePlugin {
             )
             .add_plugin(LevelPlugin)
             .add_plugin(SnakePlugin)
+            .add_plugin(SnakeBodyPlugin)
+            .add_plugin(SnakeHeadPlugin)
+            .add_plugin(ThemePlugin)
             .add_plugin(MovementPlugin)
+            .add_plugin(NpcPlugin)
+            .add_plugin(WallGroupPlugin)
+            .add_plugin(ScorePlugin)
+            .add_plugin(LevelInfoPlugin)
             .add_plugin(GameConstantsPlugin)
             .add_plugin(CameraPlugin)
             .add_plugin(DevToolsPlugin)
@@ -67,6 +86,14 @@ impl Plugin for GamePlugin {
             .insert_resource(self.args.clone())
             .insert_resource(NextLevel(self.args.level.unwrap_or(0)));
 
+        if let Some(path) = &self.args.level_file {
+            app.insert_resource(LevelFile::from_path(path));
+        }
+
+        if let Some(pack) = &self.args.pack {
+            app.insert_resource(ActivePack(format!("packs/{}.pack", pack)));
+        }
+
         //if let Some(args::Commands::Test { test_case: _ }) = self.args.command {
         //app.add_plugin(AutomatedTestPlugin);
         //}
@@ -86,6 +113,7 @@ fn enter_game_system(
     mut commands: Commands,
     args: Res<Args>,
     next_level: Res<NextLevel>,
+    level_file: Option<Res<LevelFile>>,
     // mut start_test_case_event: EventWriter<StartTestCaseEventWithIndex>,
     mut start_test_level_event: EventWriter<StartLevelEventWithLevelAssetPath>,
     mut start_level_event: EventWriter<StartLevelEventWithIndex>,
@@ -96,6 +124,19 @@ fn enter_game_system(
             // start_test_case_event.send(StartTestCaseEventWithIndex(start_test_case));
         }
         _ => {
+            // The file is only played once, the menus go back to the levels of the pack.
+            if let Some(level_file) = level_file {
+                let level_asset_path = level_file.0.clone();
+                commands.remove_resource::<LevelFile>();
+                commands.insert_resource(CurrentLevelMetadata {
+                    id: None,
+                    asset_path: level_asset_path.clone(),
+                });
+
+                start_test_level_event.send(StartLevelEventWithLevelAssetPath(level_asset_path));
+                return;
+            }
+
             if let Some(test_level) = &args.test_level {
                 let level_asset_path = format!("levels/{}", test_level);
                 commands.insert_resource(CurrentLevelMetadata {
@@ -138,7 +179,10 @@ pub fn despawn_with_system<T: Component>(mut commands: Commands, query: Query<En
 }
 
 pub fn run(app: &mut App, args: &Args) {
-    let start_state = if args.command.is_none() && args.level.is_none() && args.test_level.is_none()
+    let start_state = if args.command.is_none()
+        && args.level.is_none()
+        && args.test_level.is_none()
+        && args.level_file.is_none()
     {
         GameState::MainMenu
     } else if matches!(args.command, Some(args::Commands::Editor)) {
@@ -153,7 +197,7 @@ pub fn run(app: &mut App, args: &Args) {
             DefaultPlugins
                 .set(WindowPlugin {
                     window: WindowDescriptor {
-                        title: "CatSnake".to_string(),
+                        title: WINDOW_TITLE.to_string(),
                         width: 1920.0,
                         height: 1080.0,
                         ..default()
@@ -169,6 +213,7 @@ pub fn run(app: &mut App, args: &Args) {
         .add_plugin(MenuPlugin)
         .add_plugin(MainMenuPlugin)
         .add_plugin(SelectLevelMenuPlugin)
+        .add_plugin(LevelErrorMenuPlugin)
         .add_plugin(GamePlugin { args: args.clone() })
         .add_plugin(AudioPlugin)
         .add_plugin(LibraryPlugin)
@@ -5,7 +5,9 @@ use bevy::{
 };
 use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
 
-use crate::{level::level_instance::LevelInstance, GameState};
+use crate::{
+    level::level_instance::LevelInstance, tools::cameras::camera_3d_free::FlycamControls, GameState,
+};
 
 use super::level_entities::LevelEntity;
 
@@ -20,6 +22,7 @@ impl Plugin for CameraPlugin {
                     .run_if_resource_exists::<LevelInstance>()
                     .with_system(camera_zoom_scroll_system)
                     .with_system(camera_pan_system)
+                    .with_system(orient_camera_to_gravity_system)
                     .into(),
             );
     }
@@ -33,6 +36,49 @@ pub fn setup_camera_system(mut commands: Commands) {
     ));
 }
 
+/// The transform of a camera looking at the center of a level, with its up vector opposite to gravity.
+pub fn camera_transform_for_level(center: Vec3, gravity: IVec3) -> Transform {
+    let up = -gravity.as_vec3();
+    let side = camera_side(gravity).as_vec3();
+
+    Transform::from_translation(center + 15.0 * up + 12.0 * side).looking_at(center, up)
+}
+
+/// The side the camera looks at levels from, the usual one unless gravity is along it.
+pub fn camera_side(gravity: IVec3) -> IVec3 {
+    if gravity.z != 0 {
+        IVec3::X
+    } else {
+        IVec3::Z
+    }
+}
+
+pub fn orient_camera_to_gravity_system(
+    level_instance: Res<LevelInstance>,
+    mut last_gravity: Local<Option<IVec3>>,
+    mut camera: Query<(&mut Transform, Option<&mut FlycamControls>), With<Camera>>,
+) {
+    let gravity = level_instance.gravity();
+    let Some(previous_gravity) = last_gravity.replace(gravity) else {
+        return;
+    };
+
+    if previous_gravity == gravity {
+        return;
+    }
+
+    let Ok((mut camera_transform, fly_camera)) = camera.get_single_mut() else {
+        return;
+    };
+
+    let center: Vec3 = level_instance.compute_bounds().center.into();
+    *camera_transform = camera_transform_for_level(center, gravity);
+
+    if let Some(mut fly_camera) = fly_camera {
+        fly_camera.set_up(gravity, &camera_transform);
+    }
+}
+
 pub fn camera_zoom_scroll_system(
     mut scroll_event: EventReader<MouseWheel>,
     mut camera: Query<&mut GlobalTransform, With<Camera>>,
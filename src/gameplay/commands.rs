@@ -1,12 +1,12 @@
 use crate::{
     gameplay::movement_plugin::GravityFall,
-    gameplay::snake_plugin::Snake,
+    gameplay::snake_plugin::{Player, Snake},
     gameplay::undo::{BeginFall, EndFall, MoveHistoryEvent, SnakeHistory},
     level::level_instance::{LevelGridEntity, LevelInstance},
 };
 use bevy::prelude::*;
 
-use super::level_entities::{EntityType, GridEntity, Movable};
+use super::level_entities::{BreakableWallComponent, EntityType, GridEntity, Movable};
 
 /// Provides commands that implement the undoable game mechanics.
 /// Commands manage the state of the game data such as snakes, food, etc..
@@ -27,6 +27,7 @@ impl<'a> SnakeCommands<'a> {
 
     pub fn player_move(
         &mut self,
+        player: Player,
         snake: &'a mut Snake,
         entity: Entity,
         direction: IVec3,
@@ -39,6 +40,26 @@ impl<'a> SnakeCommands<'a> {
             pushed_entity: None,
             food: None,
             direction,
+            player: Some(player),
+        }
+    }
+
+    /// A move of a non-player snake, it is undone together with the player move that caused it.
+    pub fn npc_move(
+        &mut self,
+        snake: &'a mut Snake,
+        entity: Entity,
+        direction: IVec3,
+    ) -> PlayerMoveCommand {
+        PlayerMoveCommand {
+            level_instance: self.level_instance,
+            history: self.history,
+            snake,
+            entity,
+            pushed_entity: None,
+            food: None,
+            direction,
+            player: None,
         }
     }
 
@@ -56,6 +77,41 @@ impl<'a> SnakeCommands<'a> {
         );
     }
 
+    /// Reverse the gravity of the level when a snake reaches a gravity switch.
+    pub fn flip_gravity(&mut self, entity: Entity) {
+        let gravity = self.level_instance.gravity();
+        self.level_instance.set_gravity(-gravity);
+
+        self.history.push(
+            MoveHistoryEvent::FlipGravity(gravity),
+            LevelGridEntity::new(entity, EntityType::Snake),
+        );
+    }
+
+    /// Record a change of load on a breakable wall, storing the previous state for undo.
+    pub fn load_wall(
+        &mut self,
+        entity: Entity,
+        position: IVec3,
+        previous_state: BreakableWallComponent,
+    ) {
+        self.history.push(
+            MoveHistoryEvent::LoadWall(position, previous_state),
+            LevelGridEntity::new(entity, EntityType::BreakableWall),
+        );
+    }
+
+    /// Break a wall, the position is freed so that what was standing on it can fall.
+    pub fn break_wall(&mut self, entity: Entity, position: IVec3, state: BreakableWallComponent) {
+        let updates = self.level_instance.clear_posisitons(&[position]);
+
+        self.history.push_with_updates(
+            MoveHistoryEvent::BreakWall(position, state),
+            LevelGridEntity::new(entity, EntityType::BreakableWall),
+            updates,
+        );
+    }
+
     /// Execute a command when a skake start falling.
     pub fn start_falling(&mut self, movable: &'a dyn Movable, entity: LevelGridEntity) {
         let updates = self.level_instance.clear_posisitons(movable.positions());
@@ -125,6 +181,7 @@ pub struct PlayerMoveCommand<'a> {
     pushed_entity: Option<(LevelGridEntity, &'a mut dyn Movable)>,
     food: Option<&'a GridEntity>,
     direction: IVec3,
+    player: Option<Player>,
 }
 
 impl<'a> PlayerMoveCommand<'a> {
@@ -143,10 +200,12 @@ impl<'a> PlayerMoveCommand<'a> {
 
     pub fn execute(&mut self) {
         // Push the player action marker.
-        self.history.push(
-            MoveHistoryEvent::PlayerSnakeMove,
-            LevelGridEntity::new(self.entity, EntityType::Snake),
-        );
+        if let Some(player) = self.player {
+            self.history.push(
+                MoveHistoryEvent::PlayerSnakeMove(player),
+                LevelGridEntity::new(self.entity, EntityType::Snake),
+            );
+        }
 
         // Move the other entity.
         if let Some((pushed_entity, movable)) = &mut self.pushed_entity {
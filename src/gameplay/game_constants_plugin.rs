@@ -4,6 +4,9 @@ use bevy_inspector_egui::prelude::*;
 pub const MOVE_START_VELOCITY: f32 = 5.0;
 pub const JUMP_START_VELOCITY: f32 = 6.0;
 pub const GRAVITY: f32 = 30.0;
+pub const BREAKABLE_WALL_MAX_LOAD_TURNS: u32 = 3;
+/// The entity property overriding `BREAKABLE_WALL_MAX_LOAD_TURNS` for one wall.
+pub const MAX_LOAD_TURNS_PROPERTY: &str = "max_load_turns";
 
 macro_rules! rgb_u8 {
     ($r:expr, $g:expr, $b:expr) => {
@@ -25,8 +28,10 @@ macro_rules! rgba_u8 {
 pub const BACKGROUND_COLOR: Color = rgb_u8!(204, 217, 255);
 pub const SPIKE_COLOR: Color = Color::rgb(0.8, 0.7176471, 0.68235296);
 pub const WALL_COLOR: Color = rgb_u8!(119, 89, 54);
+pub const BREAKABLE_WALL_COLOR: Color = rgb_u8!(196, 170, 140);
 pub const WATER_COLOR: Color = rgba_u8!(27, 85, 124, 108);
 pub const FOOD_COLOR: Color = Color::rgb(0.9764706, 0.5176471, 0.2901961);
+pub const GRAVITY_SWITCH_COLOR: Color = rgb_u8!(150, 90, 200);
 
 pub const SNAKE_COLORS: [[Color; 2]; 3] = [
     [
@@ -40,6 +45,14 @@ pub const SNAKE_COLORS: [[Color; 2]; 3] = [
     [rgb_u8!(66, 135, 245), rgb_u8!(105, 159, 245)],
 ];
 
+/// Snake colors from the Okabe-Ito palette, told apart with the common color vision deficiencies.
+pub const COLOR_BLIND_SNAKE_COLORS: [[Color; 2]; 4] = [
+    [rgb_u8!(0, 114, 178), rgb_u8!(86, 180, 233)],
+    [rgb_u8!(213, 94, 0), rgb_u8!(230, 159, 0)],
+    [rgb_u8!(0, 158, 115), rgb_u8!(240, 228, 66)],
+    [rgb_u8!(204, 121, 167), rgb_u8!(240, 240, 240)],
+];
+
 #[derive(Resource, Reflect, InspectorOptions)]
 #[reflect(InspectorOptions)]
 pub struct GameConstants {
@@ -51,12 +64,6 @@ pub struct GameConstants {
 
     #[inspector(min = 0.0, max = 900.0)]
     pub gravity: f32,
-
-    pub background_color: Color,
-
-    pub ground_color: Color,
-
-    pub water_color: Color,
 }
 
 impl Default for GameConstants {
@@ -65,9 +72,6 @@ impl Default for GameConstants {
             move_velocity: MOVE_START_VELOCITY,
             jump_velocity: JUMP_START_VELOCITY,
             gravity: GRAVITY,
-            background_color: BACKGROUND_COLOR,
-            ground_color: WALL_COLOR,
-            water_color: WATER_COLOR,
         }
     }
 }
@@ -76,11 +80,6 @@ pub struct GameConstantsPlugin;
 impl Plugin for GameConstantsPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<GameConstants>()
-            .insert_resource(GameConstants::default())
-            .add_system(update_colors);
+            .insert_resource(GameConstants::default());
     }
 }
-
-fn update_colors(mut commands: Commands, game_constants: Res<GameConstants>) {
-    commands.insert_resource(ClearColor(game_constants.background_color));
-}
@@ -1,17 +1,22 @@
 use core::slice;
+use std::collections::BTreeMap;
 
-use bevy::{gltf::Gltf, prelude::*};
+use bevy::{gltf::Gltf, prelude::*, scene::SceneInstance, utils::HashMap};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    level::level_instance::{LevelGridEntity, LevelInstance},
-    library::GameAssets,
+    level::{
+        level_instance::{LevelGridEntity, LevelInstance},
+        level_template::{DecorationTemplate, EntityTemplate, Model, ModelId, PropertyValue},
+        model_catalog::{Footprint, ModelCatalog, ModelPivot},
+    },
+    library::{AssetLibrary, GameAssets},
     tools::picking::PickableBundle,
 };
 
 use super::{
-    game_constants_plugin::{FOOD_COLOR, SPIKE_COLOR},
-    snake_plugin::{Active, MaterialMeshBuilder, Snake, SnakeTemplate},
+    snake_plugin::{Active, MaterialMeshBuilder, MeshShape, Snake, SnakeTemplate},
+    theme_plugin::ThemeColor,
 };
 
 #[derive(Component)]
@@ -22,10 +27,12 @@ pub enum EntityType {
     Food,
     Spike,
     Wall,
+    BreakableWall,
     Box,
     Trigger,
     Snake,
     Goal,
+    GravitySwitch,
 }
 
 #[derive(Component, Clone, Copy)]
@@ -34,8 +41,11 @@ pub struct GridEntity {
     pub entity_type: EntityType,
 }
 
+/// Draws the default walls of a chunk of the level with a single mesh, see
+/// [`wall_groups`](super::wall_groups).
 #[derive(Component, Clone)]
 pub struct WallGroup {
+    pub chunk: IVec3,
     pub positions: Vec<IVec3>,
 }
 
@@ -52,6 +62,10 @@ trait EntityBuilder {
     fn build_entity<B: Bundle>() -> B;
 }
 
+/// A prop of the level, it has no grid position and nothing collides with it.
+#[derive(Component, Clone, Copy)]
+pub struct Decoration;
+
 #[derive(Component, Clone, Copy)]
 pub struct FoodComponent;
 
@@ -67,6 +81,17 @@ pub struct BoxComponent;
 #[derive(Component, Clone, Copy)]
 pub struct TriggerComponent;
 
+#[derive(Component, Clone, Copy)]
+pub struct GravitySwitchComponent;
+
+/// State of a fragile wall, it breaks once the load resting on it leaves,
+/// or after staying under load for too many turns.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct BreakableWallComponent {
+    pub turns_under_load: u32,
+    pub loaded: bool,
+}
+
 pub trait Movable {
     fn positions(&self) -> &[IVec3];
 
@@ -144,23 +169,12 @@ pub fn spawn_spike(
     entity
 }
 
-pub fn spawn_wall(
-    mesh_builder: &mut MaterialMeshBuilder,
-    commands: &mut Commands,
-    position: &IVec3,
-    assets: &GameAssets,
-) -> Entity {
-    let ground_material = mesh_builder.materials.add(StandardMaterial {
-        base_color: Color::rgb(0.8, 0.7, 0.6),
-        base_color_texture: Some(assets.outline_texture.clone()),
-        ..default()
-    });
-
+pub fn spawn_wall(commands: &mut Commands, position: &IVec3, assets: &GameAssets) -> Entity {
     let entity = commands
         .spawn((
             PbrBundle {
                 mesh: assets.cube_mesh.clone(),
-                material: ground_material,
+                material: assets.default_cube_material.clone(),
                 transform: Transform::from_translation(position.as_vec3()),
                 ..default()
             },
@@ -173,6 +187,43 @@ pub fn spawn_wall(
     entity
 }
 
+pub fn spawn_breakable_wall(
+    mesh_builder: &mut MaterialMeshBuilder,
+    commands: &mut Commands,
+    position: &IVec3,
+    state: BreakableWallComponent,
+) -> Entity {
+    let entity = commands
+        .spawn((
+            mesh_builder.build_breakable_wall_mesh(*position),
+            GridEntity::new(*position, EntityType::BreakableWall),
+            state,
+            LevelEntity,
+            Name::new("Breakable Wall"),
+        ))
+        .id();
+
+    entity
+}
+
+pub fn spawn_gravity_switch(
+    mesh_builder: &mut MaterialMeshBuilder,
+    commands: &mut Commands,
+    position: &IVec3,
+) -> Entity {
+    let entity = commands
+        .spawn((
+            mesh_builder.build_gravity_switch_mesh(*position),
+            GridEntity::new(*position, EntityType::GravitySwitch),
+            GravitySwitchComponent,
+            LevelEntity,
+            Name::new("Gravity Switch"),
+        ))
+        .id();
+
+    entity
+}
+
 pub fn spawn_food(
     mesh_builder: &mut MaterialMeshBuilder,
     commands: &mut Commands,
@@ -250,11 +301,183 @@ pub fn spawn_trigger(
     entity
 }
 
+/// The templates of the entities that undo can respawn, keyed by position.
+/// Food and breakable walls never move so their position finds their template.
+#[derive(Resource, Default)]
+pub struct RespawnableEntities(pub HashMap<IVec3, EntityTemplate>);
+
+/// Spawns the food and the breakable walls removed by a move again when the move is undone,
+/// with the model and the properties of their template like when the level was spawned.
+pub struct EntityRespawner<'a> {
+    pub templates: &'a RespawnableEntities,
+    pub library: &'a AssetLibrary,
+    pub assets_gltf: &'a Assets<Gltf>,
+    pub catalog: &'a ModelCatalog,
+}
+
+impl EntityRespawner<'_> {
+    pub fn respawn_food(
+        &self,
+        mesh_builder: &mut MaterialMeshBuilder,
+        commands: &mut Commands,
+        position: IVec3,
+    ) -> Entity {
+        let entity = spawn_food(mesh_builder, commands, &position);
+        self.use_template(commands, entity, position);
+        entity
+    }
+
+    pub fn respawn_breakable_wall(
+        &self,
+        mesh_builder: &mut MaterialMeshBuilder,
+        commands: &mut Commands,
+        position: IVec3,
+        state: BreakableWallComponent,
+    ) -> Entity {
+        let entity = spawn_breakable_wall(mesh_builder, commands, &position, state);
+        self.use_template(commands, entity, position);
+        entity
+    }
+
+    fn use_template(&self, commands: &mut Commands, entity: Entity, position: IVec3) {
+        let Some(template) = self.templates.0.get(&position) else {
+            return;
+        };
+
+        let footprint = use_entity_model(
+            commands,
+            entity,
+            template,
+            self.library,
+            self.assets_gltf,
+            self.catalog,
+        );
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(EntityProperties::from_template(template));
+        if let Some(footprint) = footprint {
+            entity_commands.insert(footprint);
+        }
+    }
+}
+
+/// The id and the properties of the template of a grid entity.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct EntityProperties {
+    pub id: u32,
+    pub values: BTreeMap<String, PropertyValue>,
+}
+
+impl EntityProperties {
+    pub fn from_template(template: &EntityTemplate) -> Self {
+        Self {
+            id: template.id,
+            values: template.properties.clone(),
+        }
+    }
+
+    pub fn int(&self, key: &str) -> Option<i64> {
+        match self.values.get(key)? {
+            PropertyValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Draw an entity with the model of its template instead of its default mesh.
+/// Returns the footprint of the model, `None` when the entity keeps its default mesh, as it
+/// does when the model is not loaded or has no scene.
+pub fn use_entity_model(
+    commands: &mut Commands,
+    entity: Entity,
+    template: &EntityTemplate,
+    library: &AssetLibrary,
+    assets_gltf: &Assets<Gltf>,
+    catalog: &ModelCatalog,
+) -> Option<Footprint> {
+    let Model::Asset(path) = &template.model else {
+        return None;
+    };
+
+    let model = library.models.get(path)?;
+    let scene = assets_gltf.get(model)?.scenes.first()?.clone();
+
+    let catalog_model = catalog.get(path);
+    let pivot = catalog_model.map_or(Vec3::ZERO, |catalog_model| catalog_model.pivot);
+    let transform = match catalog_model {
+        Some(catalog_model) => catalog_model.transform(template.grid_position, template.rotation),
+        None => Transform::from_translation(template.grid_position.as_vec3())
+            .with_rotation(template.rotation),
+    };
+
+    commands
+        .entity(entity)
+        .remove::<(
+            Handle<Mesh>,
+            Handle<StandardMaterial>,
+            Handle<Scene>,
+            SceneInstance,
+        )>()
+        .insert((
+            SceneBundle {
+                scene,
+                transform,
+                ..default()
+            },
+            ModelId {
+                source_asset: model.clone(),
+            },
+            ModelPivot(pivot),
+        ));
+
+    Some(catalog.footprint(path))
+}
+
+/// Decorations are not added to the `LevelInstance`, they never block the snakes.
+pub fn spawn_decoration(
+    commands: &mut Commands,
+    decoration: &DecorationTemplate,
+    library: &AssetLibrary,
+    assets_gltf: &Assets<Gltf>,
+) -> Option<Entity> {
+    let model = library.models.get(&decoration.model)?;
+    let scene = assets_gltf.get(model)?.scenes.first()?.clone();
+
+    let entity = commands
+        .spawn((
+            SceneBundle {
+                scene,
+                transform: decoration.transform(),
+                ..default()
+            },
+            ModelId {
+                source_asset: model.clone(),
+            },
+            Decoration,
+            LevelEntity,
+            Name::new("Decoration"),
+        ))
+        .id();
+
+    Some(entity)
+}
+
 impl<'a> MaterialMeshBuilder<'a> {
     pub fn build_box_mesh(&mut self, position: IVec3) -> PbrBundle {
         PbrBundle {
-            mesh: self.meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
-            material: self.materials.add(Color::BEIGE.into()),
+            mesh: self.mesh(MeshShape::Box, || Mesh::from(shape::Cube { size: 1.0 })),
+            material: self.material(ThemeColor::Box),
+            transform: Transform::from_translation(position.as_vec3()),
+            ..default()
+        }
+    }
+
+    pub fn build_breakable_wall_mesh(&mut self, position: IVec3) -> PbrBundle {
+        PbrBundle {
+            mesh: self.mesh(MeshShape::BreakableWall, || {
+                Mesh::from(shape::Cube { size: 0.95 })
+            }),
+            material: self.material(ThemeColor::BreakableWall),
             transform: Transform::from_translation(position.as_vec3()),
             ..default()
         }
@@ -262,11 +485,13 @@ impl<'a> MaterialMeshBuilder<'a> {
 
     pub fn build_food_mesh(&mut self, position: IVec3) -> PbrBundle {
         PbrBundle {
-            mesh: self.meshes.add(Mesh::from(shape::Icosphere {
-                radius: 0.3,
-                subdivisions: 5,
-            })),
-            material: self.materials.add(FOOD_COLOR.into()),
+            mesh: self.mesh(MeshShape::Food, || {
+                Mesh::from(shape::Icosphere {
+                    radius: 0.3,
+                    subdivisions: 5,
+                })
+            }),
+            material: self.material(ThemeColor::Food),
             transform: Transform::from_translation(position.as_vec3()),
             ..default()
         }
@@ -274,15 +499,31 @@ impl<'a> MaterialMeshBuilder<'a> {
 
     pub fn build_trigger_mesh(&mut self, position: IVec3) -> PbrBundle {
         PbrBundle {
-            mesh: self.meshes.add(Mesh::from(shape::Box {
-                min_x: -0.45,
-                max_x: 0.45,
-                min_y: -0.5,
-                max_y: -0.3,
-                min_z: -0.45,
-                max_z: 0.45,
-            })),
-            material: self.materials.add(Color::GRAY.into()),
+            mesh: self.mesh(MeshShape::Trigger, || {
+                Mesh::from(shape::Box {
+                    min_x: -0.45,
+                    max_x: 0.45,
+                    min_y: -0.5,
+                    max_y: -0.3,
+                    min_z: -0.45,
+                    max_z: 0.45,
+                })
+            }),
+            material: self.material(ThemeColor::Trigger),
+            transform: Transform::from_translation(position.as_vec3()),
+            ..default()
+        }
+    }
+
+    pub fn build_gravity_switch_mesh(&mut self, position: IVec3) -> PbrBundle {
+        PbrBundle {
+            mesh: self.mesh(MeshShape::GravitySwitch, || {
+                Mesh::from(shape::Icosphere {
+                    radius: 0.2,
+                    subdivisions: 3,
+                })
+            }),
+            material: self.material(ThemeColor::GravitySwitch),
             transform: Transform::from_translation(position.as_vec3()),
             ..default()
         }
@@ -290,8 +531,8 @@ impl<'a> MaterialMeshBuilder<'a> {
 
     pub fn build_spike_mesh(&mut self, position: IVec3) -> PbrBundle {
         PbrBundle {
-            mesh: self.meshes.add(Mesh::from(shape::Cube { size: 0.5 })),
-            material: self.materials.add(SPIKE_COLOR.into()),
+            mesh: self.mesh(MeshShape::Spike, || Mesh::from(shape::Cube { size: 0.5 })),
+            material: self.material(ThemeColor::Spike),
             transform: Transform::from_translation(position.as_vec3()),
             ..default()
         }
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet, IntoConditionalSystem};
+
+use crate::{
+    despawn_with_system,
+    gameplay::level_plugin::LevelLoadedEvent,
+    gameplay::movement_plugin::MoveCommandEvent,
+    gameplay::score_plugin::text_style,
+    level::level_template::{LevelMetadata, LevelTemplate, LoadedLevel, MAX_DIFFICULTY},
+    menus::FONT,
+    GameState,
+};
+
+const INTRO_SECONDS: f32 = 6.0;
+
+/// Shows the level metadata when a level starts, and its hints on demand.
+pub struct LevelInfoPlugin;
+
+impl Plugin for LevelInfoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HintProgress>()
+            .add_exit_system(GameState::Game, despawn_with_system::<LevelInfoUi>)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                show_level_intro_system
+                    .run_in_state(GameState::Game)
+                    .run_if_resource_exists::<LoadedLevel>(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Game)
+                    .run_if_resource_exists::<LoadedLevel>()
+                    .with_system(hide_level_intro_system)
+                    .with_system(show_hint_system)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct LevelInfoUi;
+
+#[derive(Component)]
+struct LevelIntro(Timer);
+
+#[derive(Component)]
+struct HintPopup;
+
+/// The index of the next hint to show in the current level.
+#[derive(Resource, Default)]
+struct HintProgress {
+    next: usize,
+}
+
+fn panel_bundle(top: f32) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(12.0),
+                top: Val::Px(top),
+                ..default()
+            },
+            max_size: Size::new(Val::Px(480.0), Val::Auto),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: BackgroundColor(Color::rgba(1.0, 1.0, 1.0, 0.8)),
+        ..default()
+    }
+}
+
+/// The lines describing the level below its title, empty when there is nothing to show.
+fn intro_details(metadata: &LevelMetadata) -> Vec<String> {
+    let mut details = vec![];
+
+    if let Some(author) = &metadata.author {
+        details.push(format!("by {}", author));
+    }
+    if let Some(difficulty) = metadata.difficulty {
+        details.push(format!("Difficulty: {}/{}", difficulty, MAX_DIFFICULTY));
+    }
+    if let Some(description) = &metadata.description {
+        details.push(description.clone());
+    }
+    if !metadata.tags.is_empty() {
+        details.push(metadata.tags.join(", "));
+    }
+    if !metadata.hints.is_empty() {
+        details.push("Press H for a hint".to_owned());
+    }
+
+    details
+}
+
+fn show_level_intro_system(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut level_loaded_event: EventReader<LevelLoadedEvent>,
+    mut hint_progress: ResMut<HintProgress>,
+    loaded_level: Res<LoadedLevel>,
+    level_templates: Res<Assets<LevelTemplate>>,
+    info_query: Query<Entity, With<LevelInfoUi>>,
+) {
+    if level_loaded_event.iter().last().is_none() {
+        return;
+    }
+
+    for entity in &info_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    hint_progress.next = 0;
+
+    let Some(metadata) = level_templates
+        .get(&loaded_level.0)
+        .map(|level| &level.metadata)
+    else {
+        return;
+    };
+
+    // Levels without a title or description don't need an introduction.
+    if metadata.title.is_none() && metadata.description.is_none() {
+        return;
+    }
+
+    let mut sections = vec![];
+    if let Some(title) = &metadata.title {
+        sections.push(TextSection::new(
+            format!("{}\n", title),
+            TextStyle {
+                font: assets.load(FONT),
+                font_size: 32.0,
+                color: Color::BLACK,
+            },
+        ));
+    }
+    sections.push(TextSection::new(
+        intro_details(metadata).join("\n"),
+        text_style(&assets),
+    ));
+
+    commands
+        .spawn((
+            panel_bundle(72.0),
+            LevelIntro(Timer::from_seconds(INTRO_SECONDS, TimerMode::Once)),
+            LevelInfoUi,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(sections));
+        });
+}
+
+/// The introduction goes away after a while or when the player starts moving.
+fn hide_level_intro_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut move_command_event: EventReader<MoveCommandEvent>,
+    mut intro_query: Query<(Entity, &mut LevelIntro)>,
+) {
+    let moved = move_command_event.iter().count() > 0;
+
+    for (entity, mut intro) in &mut intro_query {
+        if intro.0.tick(time.delta()).finished() || moved {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// H shows the hints one after the other, then hides the popup.
+fn show_hint_system(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    assets: Res<AssetServer>,
+    mut hint_progress: ResMut<HintProgress>,
+    loaded_level: Res<LoadedLevel>,
+    level_templates: Res<Assets<LevelTemplate>>,
+    popup_query: Query<Entity, With<HintPopup>>,
+) {
+    if !keyboard.just_pressed(KeyCode::H) {
+        return;
+    }
+
+    let had_popup = !popup_query.is_empty();
+    for entity in &popup_query {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let hints = level_templates
+        .get(&loaded_level.0)
+        .map(|level| level.metadata.hints.as_slice())
+        .unwrap_or_default();
+
+    if hint_progress.next >= hints.len() {
+        hint_progress.next = 0;
+
+        if had_popup {
+            return;
+        }
+    }
+
+    let text = match hints.get(hint_progress.next) {
+        Some(hint) => format!("Hint {}/{}: {}", hint_progress.next + 1, hints.len(), hint),
+        None => "This level has no hints".to_owned(),
+    };
+    hint_progress.next += 1;
+
+    commands
+        .spawn((panel_bundle(240.0), HintPopup, LevelInfoUi))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(text, text_style(&assets)));
+        });
+}
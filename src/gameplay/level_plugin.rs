@@ -1,29 +1,38 @@
-use bevy::{gltf::Gltf, pbr::NotShadowCaster, prelude::*};
+use std::path::Path;
+
+use bevy::{gltf::Gltf, pbr::NotShadowCaster, prelude::*, window::FileDragAndDrop};
 
 use iyes_loopless::{
     prelude::{AppLooplessStateExt, ConditionHelpers, ConditionSet, IntoConditionalSystem},
-    state::NextState,
+    state::{CurrentState, NextState},
 };
 
 use crate::{
+    args::Args,
+    level::ascii_level::ASCII_LEVEL_EXTENSION,
     level::level_instance::{LevelGridEntity, LevelInstance},
-    level::level_template::{LevelTemplateLoader, LoadedLevel, Model, ModelId},
+    level::level_load_error::{LevelLoadError, LevelLoadErrorKind, LevelLoadErrors},
+    level::level_template::{LevelLighting, LevelTemplateLoader, LoadedLevel, Model, ModelId},
+    level::model_catalog::{needs_single_cell, occupied_cells, ModelCatalog, ModelCatalogLoader},
     level::{
+        level_pack::{ActivePack, LevelPack, LevelPackLoader},
         level_template::{LevelTemplate, LoadingLevel},
-        levels::*,
     },
     library::{AssetLibrary, GameAssets},
     tools::cameras::camera_3d_free::FlycamControls,
-    Assets, GameState,
+    Assets, GameState, WINDOW_TITLE,
 };
 
 use super::{
+    camera_plugin::camera_transform_for_level,
     commands::SnakeCommands,
     level_entities::*,
     movement_plugin::{GravityFall, SnakeReachGoalEvent},
     movement_plugin::{LevelExitAnim, MovementStages, SnakeExitedLevelEvent},
-    snake_plugin::MaterialMeshBuilder,
-    snake_plugin::{Active, SelectedSnake, Snake},
+    npc_plugin::NpcSnake,
+    score_plugin::{star_rating, Progress},
+    snake_plugin::{Active, Player, SelectedSnake, Snake},
+    snake_plugin::{MaterialMeshBuilder, MeshMaterialCache},
     undo::SnakeHistory,
 };
 
@@ -32,14 +41,36 @@ pub struct StartLevelEventWithLevelAssetPath(pub String);
 pub struct LevelLoadedEvent;
 pub struct ClearLevelEvent;
 
+/// Sent when all the player snakes exited the level.
+pub struct LevelCompletedEvent {
+    pub asset_path: String,
+    pub moves: u32,
+    pub par: Option<u32>,
+}
+
 #[derive(Resource)]
 pub struct CurrentLevelMetadata {
     pub id: Option<usize>,
     pub asset_path: String,
 }
 
+/// A level file to play when the game starts, given on the command line or dropped on the window.
+#[derive(Resource)]
+pub struct LevelFile(pub String);
+
+impl LevelFile {
+    /// Levels outside of the assets folder are loaded with their absolute path.
+    pub fn from_path(path: &Path) -> Self {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Self(path.to_string_lossy().into_owned())
+    }
+}
+
 pub struct LevelPlugin;
 
+/// Cells of space kept around the level in the shadow box of the light.
+const SHADOW_MARGIN: f32 = 2.0;
+
 #[derive(Component, Clone, Copy)]
 pub struct Water;
 
@@ -51,13 +82,24 @@ pub enum LevelStages {
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
+        let load_errors = LevelLoadErrors::default();
+
         app.add_asset::<LevelTemplate>()
-            .init_asset_loader::<LevelTemplateLoader>()
+            .add_asset_loader(LevelTemplateLoader {
+                errors: load_errors.clone(),
+            })
+            .insert_resource(load_errors)
+            .add_asset::<LevelPack>()
+            .add_asset_loader(LevelPackLoader)
+            .add_asset::<ModelCatalog>()
+            .add_asset_loader(ModelCatalogLoader)
+            .init_resource::<ActivePack>()
             .add_exit_system(GameState::Game, clear_level_runtime_resources_system)
             .add_event::<StartLevelEventWithIndex>()
             .add_event::<StartLevelEventWithLevelAssetPath>()
             .add_event::<LevelLoadedEvent>()
             .add_event::<ClearLevelEvent>()
+            .add_event::<LevelCompletedEvent>()
             .add_system_set(
                 ConditionSet::new()
                     .run_in_state(GameState::Game)
@@ -71,6 +113,14 @@ impl Plugin for LevelPlugin {
                     .run_if_resource_exists::<LoadingLevel>()
                     .label(LevelStages::LoadLevelStage),
             )
+            // Levels dropped on the window are loaded in the editor too.
+            .add_system(
+                notify_level_loaded_system
+                    .run_in_state(GameState::Editor)
+                    .run_if_resource_exists::<LoadingLevel>(),
+            )
+            .add_system(open_dropped_level_file_system)
+            .add_system(update_window_title_system)
             .add_system_to_stage(
                 CoreStage::PreUpdate,
                 spawn_level_entities_system
@@ -116,17 +166,33 @@ impl Plugin for LevelPlugin {
     }
 }
 
+/// Start a level of the active pack, waits for the pack to be loaded.
 fn load_level_with_index_system(
     mut commands: Commands,
+    mut pending_level_index: Local<Option<usize>>,
+    active_pack: Res<ActivePack>,
+    library: Res<AssetLibrary>,
+    packs: Res<Assets<LevelPack>>,
     mut event_start_level_with_index: EventReader<StartLevelEventWithIndex>,
     mut event_start_level: EventWriter<StartLevelEventWithLevelAssetPath>,
 ) {
-    let Some(event) = event_start_level_with_index.iter().next() else {
+    if let Some(event) = event_start_level_with_index.iter().next() {
+        *pending_level_index = Some(event.0);
+    }
+
+    let Some(pack) = active_pack.get(&library, &packs) else {
+        return;
+    };
+
+    let Some(next_level_index) = pending_level_index.take() else {
         return;
     };
 
-    let next_level_index = event.0;
-    let level_asset_path = format!("levels/{}", LEVELS[next_level_index]);
+    let Some(level_asset_path) = pack.level(next_level_index).map(str::to_owned) else {
+        error!("{} has no level {}", active_pack.0, next_level_index);
+        commands.insert_resource(NextState(GameState::SelectLevelMenu));
+        return;
+    };
 
     event_start_level.send(StartLevelEventWithLevelAssetPath(level_asset_path.clone()));
 
@@ -153,6 +219,7 @@ fn notify_level_loaded_system(
     mut commands: Commands,
     level_loading: Res<LoadingLevel>,
     asset_server: Res<AssetServer>,
+    load_errors: Res<LevelLoadErrors>,
     mut level_loaded_event: EventWriter<LevelLoadedEvent>,
 ) {
     let load_state = asset_server.get_load_state(&level_loading.0);
@@ -166,14 +233,204 @@ fn notify_level_loaded_system(
 
             level_loaded_event.send(LevelLoadedEvent);
         }
-        bevy::asset::LoadState::Failed => panic!("Failed loading level"),
+        bevy::asset::LoadState::Failed => {
+            commands.remove_resource::<LoadingLevel>();
+
+            let file = asset_server
+                .get_handle_path(&level_loading.0)
+                .map(|path| path.path().to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let error = load_errors
+                .take(&file)
+                .unwrap_or_else(|| LevelLoadError::new(&file, LevelLoadErrorKind::Unreadable));
+
+            show_level_load_error(&mut commands, error);
+        }
         _ => {}
     }
 }
 
-pub fn clear_level_runtime_resources_system(mut commands: Commands) {
+/// Open a level file dropped on the window, in the editor when it is open, else in the game.
+#[allow(clippy::too_many_arguments)]
+fn open_dropped_level_file_system(
+    mut commands: Commands,
+    state: Res<CurrentState<GameState>>,
+    asset_server: Res<AssetServer>,
+    mut file_drag_and_drop_event: EventReader<FileDragAndDrop>,
+    mut event_clear_level: EventWriter<ClearLevelEvent>,
+    mut event_start_level: EventWriter<StartLevelEventWithLevelAssetPath>,
+    level_entities: Query<Entity, (With<LevelEntity>, Without<Camera>)>,
+) {
+    let dropped_level = file_drag_and_drop_event
+        .iter()
+        .filter_map(|event| match event {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => Some(path_buf),
+            _ => None,
+        })
+        .filter(|path| {
+            path.extension().map_or(false, |extension| {
+                extension == "lvl" || extension == ASCII_LEVEL_EXTENSION
+            })
+        })
+        .last();
+
+    let Some(path) = dropped_level else {
+        return;
+    };
+
+    let LevelFile(asset_path) = LevelFile::from_path(path);
+
+    match state.0 {
+        GameState::Game => {
+            event_clear_level.send(ClearLevelEvent);
+            event_start_level.send(StartLevelEventWithLevelAssetPath(asset_path.clone()));
+        }
+        GameState::Editor => {
+            for entity in &level_entities {
+                commands.entity(entity).despawn_recursive();
+            }
+            commands.insert_resource(LoadingLevel(asset_server.load(&asset_path)));
+        }
+        _ => {
+            commands.insert_resource(LevelFile(asset_path));
+            commands.insert_resource(NextState(GameState::Game));
+            return;
+        }
+    }
+
+    commands.insert_resource(CurrentLevelMetadata {
+        id: None,
+        asset_path,
+    });
+}
+
+/// Show the file of the current level in the window title.
+fn update_window_title_system(
+    mut windows: ResMut<Windows>,
+    level_meta: Option<Res<CurrentLevelMetadata>>,
+) {
+    let Some(level_meta) = level_meta else {
+        return;
+    };
+
+    if !level_meta.is_changed() {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!("{} - {}", WINDOW_TITLE, level_meta.asset_path));
+    }
+}
+
+/// Leave the level for the error screen.
+pub fn show_level_load_error(commands: &mut Commands, error: LevelLoadError) {
+    error!("Failed loading level {}", error);
+
+    commands.insert_resource(error);
+    commands.insert_resource(NextState(GameState::LevelError));
+}
+
+/// Check that the models used by the level exist.
+fn validate_level_models(
+    level_template: &LevelTemplate,
+    library: &AssetLibrary,
+    catalog: &ModelCatalog,
+    file: &str,
+) -> Result<(), LevelLoadError> {
+    for (index, entity) in level_template.entities.iter().enumerate() {
+        if let Model::Asset(path) = &entity.model {
+            let kind = if !library.models.contains_key(path) {
+                LevelLoadErrorKind::MissingModel(path.clone())
+            } else if needs_single_cell(entity.entity_type)
+                && !catalog.footprint(path).is_single_cell()
+            {
+                LevelLoadErrorKind::MultiCellModel(path.clone(), entity.entity_type)
+            } else if !catalog.allows(path, entity.entity_type) {
+                LevelLoadErrorKind::ModelNotAllowed(path.clone(), entity.entity_type)
+            } else {
+                continue;
+            };
+
+            return Err(LevelLoadError::new(file, kind).with_entity(level_template, index));
+        }
+    }
+
+    for decoration in &level_template.decorations {
+        if !library.models.contains_key(&decoration.model) {
+            return Err(LevelLoadError::new(
+                file,
+                LevelLoadErrorKind::MissingModel(decoration.model.clone()),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn clear_level_runtime_resources_system(mut commands: Commands, cache: Res<MeshMaterialCache>) {
     commands.remove_resource::<LevelInstance>();
     commands.remove_resource::<SnakeHistory>();
+    commands.insert_resource(AmbientLight::default());
+    commands.insert_resource(ClearColor(cache.theme().background));
+}
+
+/// The sun of the level, its shadow box covers every cell between `min` and `max`.
+fn level_light(lighting: &LevelLighting, min: IVec3, max: IVec3) -> DirectionalLightBundle {
+    let (min, max) = if min.cmple(max).all() {
+        (min.as_vec3(), max.as_vec3())
+    } else {
+        (Vec3::ZERO, Vec3::ZERO)
+    };
+    let min = min - Vec3::splat(SHADOW_MARGIN);
+    let max = max + Vec3::splat(SHADOW_MARGIN);
+    let center = 0.5 * (min + max);
+
+    let direction = lighting
+        .direction
+        .try_normalize()
+        .unwrap_or_else(|| LevelLighting::default().direction);
+    let up = if direction.cross(Vec3::Y).length_squared() < 1e-4 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let transform = Transform::from_translation(center).looking_at(center + direction, up);
+
+    // The bounds of the level seen from the light, which looks along -z.
+    let view = transform.compute_matrix().inverse();
+    let mut light_min = Vec3::splat(f32::MAX);
+    let mut light_max = Vec3::splat(f32::MIN);
+    for corner in 0..8 {
+        let point = Vec3::select(
+            BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            max,
+            min,
+        );
+        let point = view.transform_point3(point);
+        light_min = light_min.min(point);
+        light_max = light_max.max(point);
+    }
+
+    DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            color: lighting.color,
+            illuminance: lighting.illuminance,
+            shadows_enabled: true,
+            shadow_projection: OrthographicProjection {
+                left: light_min.x,
+                right: light_max.x,
+                bottom: light_min.y,
+                top: light_max.y,
+                near: -light_max.z,
+                far: -light_min.z,
+                ..default()
+            },
+            ..default()
+        },
+        transform,
+        ..default()
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -183,11 +440,15 @@ pub fn spawn_level_entities_system(
     mut level_instance: ResMut<LevelInstance>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
     assets_gltf: Res<Assets<Gltf>>,
     assets: Res<GameAssets>,
     library: Res<AssetLibrary>,
+    catalogs: Res<Assets<ModelCatalog>>,
     loaded_level: Res<LoadedLevel>,
     level_templates: ResMut<Assets<LevelTemplate>>,
+    level_meta: Option<Res<CurrentLevelMetadata>>,
+    args: Res<Args>,
     mut camera: Query<(&mut Transform, Option<&mut FlycamControls>), With<Camera>>,
 ) {
     if level_loaded_event.is_empty() {
@@ -199,6 +460,17 @@ pub fn spawn_level_entities_system(
         .get(&loaded_level.0)
         .expect("Level should be loaded here!");
 
+    let file = level_meta
+        .map(|level_meta| level_meta.asset_path.clone())
+        .unwrap_or_default();
+
+    let catalog = ModelCatalog::of_library(&library, &catalogs);
+
+    if let Err(error) = validate_level_models(level_template, &library, catalog, &file) {
+        show_level_load_error(&mut commands, error);
+        return;
+    }
+
     let mut min = 1000 * IVec3::ONE;
     let mut max = 1000 * IVec3::NEG_ONE;
 
@@ -209,45 +481,55 @@ pub fn spawn_level_entities_system(
 
     let center = min.as_vec3() + 0.5 * (max - min).as_vec3();
 
+    level_instance.set_gravity(level_template.gravity);
+    level_instance.set_planar(level_template.planar);
+
     let (mut camera_transform, fly_camera) = camera.single_mut();
-    *camera_transform = Transform::from_translation(center + 15.0 * Vec3::Y + 12.0 * Vec3::Z)
-        .looking_at(center, Vec3::Y);
+    *camera_transform = camera_transform_for_level(center, level_template.gravity);
 
     if let Some(mut fly_camera) = fly_camera {
-        fly_camera.set_transform(&camera_transform);
+        fly_camera.set_up(level_template.gravity, &camera_transform);
     }
 
     // light
-    let size = 25.0;
-    commands.spawn((
-        DirectionalLightBundle {
-            directional_light: DirectionalLight {
-                color: Color::rgb(1.0, 1.0, 1.0),
-                illuminance: 10000.0,
-                shadows_enabled: true,
-                shadow_projection: OrthographicProjection {
-                    left: -size,
-                    right: size,
-                    bottom: -size,
-                    top: size,
-                    near: -size,
-                    far: size,
-                    ..Default::default()
-                },
-                ..default()
-            },
-            transform: Transform::from_translation(Vec3::new(0.5, 3.0, 0.5))
-                .looking_at(Vec3::ZERO, Vec3::Y),
-            ..default()
-        },
-        LevelEntity,
+    let snake_cells = level_template
+        .snakes
+        .iter()
+        .chain(level_template.npc_snakes.iter().map(|npc| &npc.parts))
+        .flatten()
+        .map(|(position, _)| *position);
+    let (cells_min, cells_max) = level_template
+        .entities
+        .iter()
+        .map(|entity| entity.grid_position)
+        .chain(snake_cells)
+        .fold(
+            (IVec3::splat(i32::MAX), IVec3::splat(i32::MIN)),
+            |(min, max), cell| (min.min(cell), max.max(cell)),
+        );
+
+    let lighting = &level_template.lighting;
+    commands.spawn((level_light(lighting, cells_min, cells_max), LevelEntity));
+    commands.insert_resource(AmbientLight {
+        color: lighting.ambient_color,
+        brightness: lighting.ambient_brightness,
+    });
+    commands.insert_resource(ClearColor(
+        lighting
+            .background_color
+            .unwrap_or(cache.theme().background),
     ));
 
+    cache.set_snake_colors(&level_template.snake_colors, &mut materials);
+
     let mut mesh_builder = MaterialMeshBuilder {
         meshes: meshes.as_mut(),
         materials: materials.as_mut(),
+        cache: cache.as_mut(),
     };
 
+    let mut respawnable_entities = RespawnableEntities::default();
+
     // Spawn the entities
     for entity_template in &level_template.entities {
         let transform = Transform::from_translation(entity_template.grid_position.as_vec3())
@@ -264,41 +546,25 @@ pub fn spawn_level_entities_system(
                 &mut commands,
                 &entity_template.grid_position,
             ),
-            EntityType::Wall => {
-                let mut entity_command = commands.spawn((
+            EntityType::Wall => commands
+                .spawn((
+                    PbrBundle {
+                        mesh: assets.cube_mesh.clone(),
+                        material: assets.default_cube_material.clone(),
+                        transform,
+                        ..default()
+                    },
                     LevelEntity,
                     GridEntity::new(entity_template.grid_position, EntityType::Wall),
                     Name::new("Wall"),
-                ));
-
-                match &entity_template.model {
-                    Model::Default(_) => {
-                        entity_command.insert(PbrBundle {
-                            mesh: assets.cube_mesh.clone(),
-                            material: assets.default_cube_material.clone(),
-                            transform,
-                            ..default()
-                        });
-                    }
-                    Model::Asset(path) => {
-                        let model = library.models.get(path).unwrap();
-                        let scene = assets_gltf.get(model).unwrap().scenes[0].clone();
-
-                        entity_command.insert((
-                            SceneBundle {
-                                scene,
-                                transform,
-                                ..default()
-                            },
-                            ModelId {
-                                source_asset: model.clone(),
-                            },
-                        ));
-                    }
-                }
-
-                entity_command.id()
-            }
+                ))
+                .id(),
+            EntityType::BreakableWall => spawn_breakable_wall(
+                &mut mesh_builder,
+                &mut commands,
+                &entity_template.grid_position,
+                BreakableWallComponent::default(),
+            ),
             EntityType::Box => spawn_box(
                 &mut mesh_builder,
                 &mut commands,
@@ -315,13 +581,60 @@ pub fn spawn_level_entities_system(
                 &assets,
                 &assets_gltf,
             ),
-            EntityType::Snake => todo!(),
+            EntityType::GravitySwitch => spawn_gravity_switch(
+                &mut mesh_builder,
+                &mut commands,
+                &entity_template.grid_position,
+            ),
+            // Rejected when the level is loaded.
+            EntityType::Snake => continue,
         };
 
-        level_instance.mark_position_occupied(
+        // Any entity can name a model, the default mesh stays when it has none.
+        let footprint = use_entity_model(
+            &mut commands,
+            entity,
+            entity_template,
+            &library,
+            &assets_gltf,
+            catalog,
+        );
+
+        if matches!(
+            entity_template.entity_type,
+            EntityType::Food | EntityType::BreakableWall
+        ) {
+            respawnable_entities
+                .0
+                .insert(entity_template.grid_position, entity_template.clone());
+        }
+
+        commands
+            .entity(entity)
+            .insert(EntityProperties::from_template(entity_template));
+
+        // Multi-cell models block every cell of their footprint.
+        let cells = occupied_cells(
             entity_template.grid_position,
-            LevelGridEntity::new(entity, entity_template.entity_type),
+            footprint.as_ref(),
+            entity_template.rotation,
         );
+        for cell in cells {
+            level_instance.mark_position_occupied(
+                cell,
+                LevelGridEntity::new(entity, entity_template.entity_type),
+            );
+        }
+
+        if let Some(footprint) = footprint {
+            commands.entity(entity).insert(footprint);
+        }
+    }
+
+    commands.insert_resource(respawnable_entities);
+
+    for decoration in &level_template.decorations {
+        spawn_decoration(&mut commands, decoration, &library, &assets_gltf);
     }
 
     for (snake_index, snake_template) in level_template.snakes.iter().enumerate() {
@@ -334,9 +647,28 @@ pub fn spawn_level_entities_system(
         );
 
         if snake_index == 0 {
-            commands.entity(entity).insert(SelectedSnake);
+            commands.entity(entity).insert(SelectedSnake(Player::One));
+        } else if snake_index == 1 && args.coop {
+            commands.entity(entity).insert(SelectedSnake(Player::Two));
         }
     }
+
+    for (npc_index, npc_template) in level_template.npc_snakes.iter().enumerate() {
+        let entity = spawn_snake(
+            &mut mesh_builder,
+            &mut commands,
+            &mut level_instance,
+            &npc_template.parts,
+            (level_template.snakes.len() + npc_index) as i32,
+        );
+
+        commands.entity(entity).insert((
+            NpcSnake {
+                behaviour: npc_template.behaviour.clone(),
+            },
+            Name::new("Npc Snake"),
+        ));
+    }
 }
 
 pub fn clear_level_system(
@@ -354,6 +686,7 @@ pub fn clear_level_system(
 
     commands.remove_resource::<LevelInstance>();
     commands.remove_resource::<SnakeHistory>();
+    commands.remove_resource::<RespawnableEntities>();
 }
 
 fn _activate_goal_when_all_food_eaten_system(
@@ -383,17 +716,30 @@ fn activate_goal_when_trigger_pressed_system(
     triggers_query: Query<&TriggerComponent, Without<Active>>,
     assets: Res<GameAssets>,
     gltfs: Res<Assets<Gltf>>,
-    mut goal_query: Query<(Entity, Option<&Active>, &mut Handle<Scene>), With<GoalComponent>>,
+    mut goal_query: Query<
+        (
+            Entity,
+            Option<&Active>,
+            &mut Handle<Scene>,
+            Option<&ModelId>,
+        ),
+        With<GoalComponent>,
+    >,
     light_cone: Query<Entity, With<LightCone>>,
 ) {
-    let Ok((goal_entity, active, mut scene)) = goal_query.get_single_mut() else {
+    let Ok((goal_entity, active, mut scene, custom_model)) = goal_query.get_single_mut() else {
         return;
     };
 
+    // Goals drawn with a custom model keep it, only the light shows they are open.
+    let swap_scene = custom_model.is_none();
+
     if triggers_query.is_empty() {
         if active.is_none() {
             commands.entity(goal_entity).insert(Active);
-            *scene = gltfs.get(&assets.goal_active_mesh).unwrap().scenes[0].clone();
+            if swap_scene {
+                *scene = gltfs.get(&assets.goal_active_mesh).unwrap().scenes[0].clone();
+            }
 
             commands.entity(goal_entity).with_children(|parent| {
                 parent.spawn((
@@ -409,7 +755,9 @@ fn activate_goal_when_trigger_pressed_system(
         }
     } else if active.is_some() {
         commands.entity(goal_entity).remove::<Active>();
-        *scene = gltfs.get(&assets.goal_inactive_mesh).unwrap().scenes[0].clone();
+        if swap_scene {
+            *scene = gltfs.get(&assets.goal_inactive_mesh).unwrap().scenes[0].clone();
+        }
 
         commands.entity(light_cone.single()).despawn();
     }
@@ -418,7 +766,10 @@ fn activate_goal_when_trigger_pressed_system(
 #[allow(clippy::type_complexity)]
 pub fn check_for_level_completion_system(
     mut snake_reach_goal_event: EventWriter<SnakeReachGoalEvent>,
-    snakes_query: Query<(Entity, &Snake), (With<Active>, Without<LevelExitAnim>)>,
+    snakes_query: Query<
+        (Entity, &Snake),
+        (With<Active>, Without<LevelExitAnim>, Without<NpcSnake>),
+    >,
     goal_query: Query<&GridEntity, (With<GoalComponent>, With<Active>)>,
 ) {
     let Ok(goal) = goal_query.get_single() else {
@@ -443,7 +794,7 @@ pub fn start_snake_exit_level_system(
     mut commands: Commands,
     snakes_query: Query<
         (Entity, &Snake, Option<&GravityFall>, Option<&SelectedSnake>),
-        With<Active>,
+        (With<Active>, Without<NpcSnake>),
     >,
 ) {
     if let Some(reach_goal_event) = snake_reach_goal_event.iter().next() {
@@ -459,14 +810,16 @@ pub fn start_snake_exit_level_system(
         SnakeCommands::new(level_instance.as_mut(), history.as_mut())
             .exit_level(snake, entity, gravity);
 
-        // Select another snake if the snake was selected.
-        if selected_snake.is_some() {
-            let other_snake = snakes_query
-                .iter()
-                .find(|(other_entity, _, _, _)| entity != *other_entity);
+        // Give the player another snake if the snake was selected.
+        if let Some(SelectedSnake(player)) = selected_snake {
+            let other_snake = snakes_query.iter().find(|(other_entity, _, _, selected)| {
+                entity != *other_entity && selected.is_none()
+            });
 
             if let Some((next_snake_entity, _, _, _)) = other_snake {
-                commands.entity(next_snake_entity).insert(SelectedSnake);
+                commands
+                    .entity(next_snake_entity)
+                    .insert(SelectedSnake(*player));
             }
         }
 
@@ -480,27 +833,58 @@ pub fn start_snake_exit_level_system(
     snake_reach_goal_event.clear();
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn finish_snake_exit_level_system(
     mut commands: Commands,
     level_meta: Res<CurrentLevelMetadata>,
+    history: Res<SnakeHistory>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    level_templates: Res<Assets<LevelTemplate>>,
     snake_reach_goal_event: EventReader<SnakeExitedLevelEvent>,
     mut event_start_level: EventWriter<StartLevelEventWithIndex>,
     mut event_clear_level: EventWriter<ClearLevelEvent>,
-    snakes_query: Query<&Snake, With<Active>>,
+    mut event_level_completed: EventWriter<LevelCompletedEvent>,
+    snakes_query: Query<&Snake, (With<Active>, Without<NpcSnake>)>,
+    active_pack: Res<ActivePack>,
+    library: Res<AssetLibrary>,
+    packs: Res<Assets<LevelPack>>,
+    progress: Res<Progress>,
 ) {
     if snake_reach_goal_event.is_empty() {
         return;
     }
 
     if snakes_query.is_empty() {
+        let completed = LevelCompletedEvent {
+            asset_path: level_meta.asset_path.clone(),
+            moves: history.player_move_count(),
+            par: loaded_level
+                .and_then(|level| level_templates.get(&level.0))
+                .and_then(|template| template.par),
+        };
+
         if let Some(level_id) = level_meta.id {
-            if level_id == LEVELS.len() - 1 {
-                event_clear_level.send(ClearLevelEvent);
-                commands.insert_resource(NextState(GameState::MainMenu));
-            } else {
-                event_clear_level.send(ClearLevelEvent);
-                event_start_level.send(StartLevelEventWithIndex(level_id + 1));
+            let next_level_id = level_id + 1;
+            event_clear_level.send(ClearLevelEvent);
+
+            // The progress is only recorded from the completed event, count this level already.
+            let mut progress = progress.clone();
+            let stars = star_rating(completed.moves, completed.par);
+            progress.record(&completed.asset_path, completed.moves, stars);
+
+            match active_pack.get(&library, &packs) {
+                Some(pack) if next_level_id >= pack.level_count() => {
+                    commands.insert_resource(NextState(GameState::MainMenu));
+                }
+                Some(pack) if !pack.is_level_unlocked(next_level_id, &progress) => {
+                    commands.insert_resource(NextState(GameState::SelectLevelMenu));
+                }
+                _ => {
+                    event_start_level.send(StartLevelEventWithIndex(next_level_id));
+                }
             }
         }
+
+        event_level_completed.send(completed);
     }
 }
@@ -2,7 +2,14 @@ pub mod camera_plugin;
 pub mod commands;
 pub mod game_constants_plugin;
 pub mod level_entities;
+pub mod level_info_plugin;
 pub mod level_plugin;
 pub mod movement_plugin;
+pub mod npc_plugin;
+pub mod score_plugin;
+pub mod snake_body;
+pub mod snake_head;
 pub mod snake_plugin;
+pub mod theme_plugin;
 pub mod undo;
+pub mod wall_groups;
@@ -1,4 +1,8 @@
-use bevy::{ecs::query::ReadOnlyWorldQuery, prelude::*, utils::HashMap};
+use bevy::{
+    ecs::query::ReadOnlyWorldQuery,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 use bevy_kira_audio::{Audio, AudioControl};
 use bevy_tweening::{
     component_animator_system, AnimationSystem, Animator, EaseFunction, Lens, Tween,
@@ -7,9 +11,11 @@ use iyes_loopless::prelude::{ConditionSet, IntoConditionalSystem};
 use rand::prelude::*;
 
 use crate::{
+    args::Args,
+    gameplay::camera_plugin::camera_side,
     gameplay::commands::SnakeCommands,
     gameplay::game_constants_plugin::*,
-    gameplay::snake_plugin::{respawn_snake_on_fall_system, Active, SelectedSnake, Snake},
+    gameplay::snake_plugin::{respawn_snake_on_fall_system, Active, Player, SelectedSnake, Snake},
     gameplay::undo::{keyboard_undo_system, undo_event_system, SnakeHistory, UndoEvent},
     level::level_instance::{LevelGridEntity, LevelInstance},
     library::GameAssets,
@@ -19,16 +25,40 @@ use crate::{
 use super::{
     level_entities::*,
     snake_plugin::{
-        DespawnSnakePartEvent, MaterialMeshBuilder, PartClipper, SnakeElement, SnakePart,
+        DespawnSnakePartEvent, MaterialMeshBuilder, MeshMaterialCache, PartClipper, SnakeElement,
+        SnakePart,
     },
 };
 
-const MOVE_UP_KEYS: [KeyCode; 2] = [KeyCode::W, KeyCode::Up];
-const MOVE_LEFT_KEYS: [KeyCode; 2] = [KeyCode::A, KeyCode::Left];
-const MOVE_DOWN_KEYS: [KeyCode; 2] = [KeyCode::S, KeyCode::Down];
-const MOVE_RIGHT_KEYS: [KeyCode; 2] = [KeyCode::D, KeyCode::Right];
-const RISE_KEYS: [KeyCode; 2] = [KeyCode::E, KeyCode::Space];
-const DIVE_KEYS: [KeyCode; 2] = [KeyCode::Q, KeyCode::LControl];
+/// The keys to move up, left, down, right, rise and dive.
+type MoveKeys = [&'static [KeyCode]; 6];
+
+const SOLO_MOVE_KEYS: MoveKeys = [
+    &[KeyCode::W, KeyCode::Up],
+    &[KeyCode::A, KeyCode::Left],
+    &[KeyCode::S, KeyCode::Down],
+    &[KeyCode::D, KeyCode::Right],
+    &[KeyCode::E, KeyCode::Space],
+    &[KeyCode::Q, KeyCode::LControl],
+];
+
+const PLAYER_ONE_MOVE_KEYS: MoveKeys = [
+    &[KeyCode::W],
+    &[KeyCode::A],
+    &[KeyCode::S],
+    &[KeyCode::D],
+    &[KeyCode::E, KeyCode::Space],
+    &[KeyCode::Q, KeyCode::LControl],
+];
+
+const PLAYER_TWO_MOVE_KEYS: MoveKeys = [
+    &[KeyCode::Up],
+    &[KeyCode::Left],
+    &[KeyCode::Down],
+    &[KeyCode::Right],
+    &[KeyCode::RShift],
+    &[KeyCode::RControl],
+];
 
 #[derive(Component, Default)]
 pub struct MoveCommand {
@@ -36,6 +66,15 @@ pub struct MoveCommand {
     pub lerp_time: f32,
 }
 
+impl MoveCommand {
+    pub fn new(velocity: f32) -> Self {
+        Self {
+            velocity,
+            lerp_time: 0.0,
+        }
+    }
+}
+
 #[derive(Component, Default)]
 pub struct PushedAnim {
     pub direction: Vec3,
@@ -43,6 +82,16 @@ pub struct PushedAnim {
     pub lerp_time: f32,
 }
 
+impl PushedAnim {
+    pub fn new(direction: Vec3, velocity: f32) -> Self {
+        Self {
+            direction,
+            velocity,
+            lerp_time: 0.0,
+        }
+    }
+}
+
 #[derive(Component, Copy, Clone)]
 pub struct GravityFall {
     velocity: f32,
@@ -56,6 +105,10 @@ pub struct LevelExitAnim {
     pub initial_snake_position: Vec<SnakeElement>,
 }
 
+/// How far a pressed trigger sank, along the gravity of when it was pressed.
+#[derive(Component)]
+pub struct PressedTrigger(Vec3);
+
 #[derive(Component)]
 pub struct PartGrowAnim {
     pub grow_factor: f32,
@@ -71,12 +124,15 @@ impl Lens<PartGrowAnim> for GrowPartLens {
 
 pub struct MovementPlugin;
 
-pub struct MoveCommandEvent(pub IVec3);
+pub struct MoveCommandEvent(pub IVec3, pub Player);
 
-pub struct SnakeMovedEvent;
+pub struct SnakeMovedEvent(pub Entity);
 
 pub struct SnakeReachGoalEvent(pub Entity);
 
+/// A snake or a box moved a cell down with gravity, or landed.
+pub struct MovableFellEvent(pub Entity);
+
 pub struct SnakeExitedLevelEvent;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel, StageLabel)]
@@ -84,7 +140,9 @@ pub enum MovementStages {
     KeyboardInput,
     Undo,
     SnakeMovement,
+    NpcMovement,
     SnakeGrow,
+    GravitySwitch,
     SnakeFall,
     SmoothMovement,
 }
@@ -92,6 +150,7 @@ pub enum MovementStages {
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SnakeMovedEvent>()
+            .add_event::<MovableFellEvent>()
             .add_event::<MoveCommandEvent>()
             .add_event::<SnakeReachGoalEvent>()
             .add_event::<SnakeExitedLevelEvent>()
@@ -109,6 +168,7 @@ impl Plugin for MovementPlugin {
                 ConditionSet::new()
                     .run_in_state(GameState::Game)
                     .run_if_resource_exists::<LevelInstance>()
+                    .run_if_resource_exists::<RespawnableEntities>()
                     .label(MovementStages::Undo)
                     .after(MovementStages::KeyboardInput)
                     .with_system(undo_event_system)
@@ -129,6 +189,22 @@ impl Plugin for MovementPlugin {
                     .label(MovementStages::SnakeGrow)
                     .after(MovementStages::SnakeMovement),
             )
+            .add_system(
+                flip_gravity_on_switch_system
+                    .run_in_state(GameState::Game)
+                    .run_if_resource_exists::<LevelInstance>()
+                    .label(MovementStages::GravitySwitch)
+                    .after(MovementStages::SnakeMovement)
+                    .before(MovementStages::SnakeFall),
+            )
+            .add_system(
+                crumble_breakable_walls_system
+                    .run_in_state(GameState::Game)
+                    .run_if_resource_exists::<LevelInstance>()
+                    .after(MovementStages::GravitySwitch)
+                    .after(MovementStages::NpcMovement)
+                    .before(MovementStages::SnakeFall),
+            )
             .add_system_set(
                 ConditionSet::new()
                     .run_in_state(GameState::Game)
@@ -172,41 +248,57 @@ fn min_distance_to_ground(
         .unwrap()
 }
 
+/// The directions of the move keys as seen from the camera, which turns with gravity.
+fn move_directions(gravity: IVec3, planar: bool) -> [IVec3; 6] {
+    let up = -gravity;
+    let away_from_camera = -camera_side(gravity);
+    let right = away_from_camera.cross(up);
+
+    // Planar levels have no depth, forward and back move up and down instead.
+    let forward = if planar { up } else { away_from_camera };
+
+    [forward, -right, -forward, right, up, -up]
+}
+
+fn pressed_move_direction(
+    keyboard: &Input<KeyCode>,
+    keys: &MoveKeys,
+    directions: [IVec3; 6],
+) -> Option<IVec3> {
+    keys.iter()
+        .zip(directions)
+        .find(|(keys, _)| keyboard.any_just_pressed(keys.iter().copied()))
+        .map(|(_, direction)| direction)
+}
+
 pub fn keyboard_move_command_system(
     keyboard: Res<Input<KeyCode>>,
+    args: Res<Args>,
+    level_instance: Res<LevelInstance>,
     mut move_command_event: EventWriter<MoveCommandEvent>,
 ) {
-    let new_direction = if keyboard.any_just_pressed(MOVE_UP_KEYS) {
-        Some(IVec3::NEG_Z)
-    } else if keyboard.any_just_pressed(MOVE_LEFT_KEYS) {
-        Some(IVec3::NEG_X)
-    } else if keyboard.any_just_pressed(MOVE_DOWN_KEYS) {
-        Some(IVec3::Z)
-    } else if keyboard.any_just_pressed(MOVE_RIGHT_KEYS) {
-        Some(IVec3::X)
-    } else if keyboard.any_just_pressed(RISE_KEYS) {
-        Some(IVec3::Y)
-    } else if keyboard.any_just_pressed(DIVE_KEYS) {
-        Some(IVec3::NEG_Y)
-    } else {
-        None
-    };
+    let directions = move_directions(level_instance.gravity(), level_instance.is_planar());
 
-    let Some(direction) = new_direction else {
+    if !args.coop {
+        if let Some(direction) = pressed_move_direction(&keyboard, &SOLO_MOVE_KEYS, directions) {
+            move_command_event.send(MoveCommandEvent(direction, Player::One));
+        }
         return;
-    };
+    }
 
-    move_command_event.send(MoveCommandEvent(direction));
+    for (player, keys) in [
+        (Player::One, &PLAYER_ONE_MOVE_KEYS),
+        (Player::Two, &PLAYER_TWO_MOVE_KEYS),
+    ] {
+        if let Some(direction) = pressed_move_direction(&keyboard, keys, directions) {
+            move_command_event.send(MoveCommandEvent(direction, player));
+        }
+    }
 }
 
-type WithMovementControlSystemFilter = (
-    With<SelectedSnake>,
-    With<Active>,
-    Without<MoveCommand>,
-    Without<GravityFall>,
-);
+type WithMovementControlSystemFilter = (With<Active>, Without<MoveCommand>, Without<GravityFall>);
 
-fn snake_can_move_forward(
+pub fn snake_can_move_forward(
     level_instance: &LevelInstance,
     snake: &Snake,
     other_entity: &Option<(Entity, &dyn Movable)>,
@@ -258,6 +350,14 @@ impl<'a> MovableRegistry<'a> {
         }
     }
 
+    pub fn contains(&self, entity: &LevelGridEntity) -> bool {
+        match entity.entity_type {
+            EntityType::Box => self.box_registry.contains_key(&entity.entity),
+            EntityType::Snake => self.snake_registry.contains_key(&entity.entity),
+            _ => false,
+        }
+    }
+
     pub fn get(&mut self, entity: &LevelGridEntity) -> &dyn Movable {
         match entity.entity_type {
             EntityType::Box => self.box_registry.get(&entity.entity).expect("msg").as_ref(),
@@ -298,6 +398,11 @@ impl<'a> MovableRegistry<'a> {
             _ => panic!("Should not happen"),
         }
     }
+
+    /// Remove a snake from the registry, so it can move while the others are pushed.
+    pub fn take_snake(&mut self, entity: Entity) -> Option<Mut<'a, Snake>> {
+        self.snake_registry.remove(&entity)
+    }
 }
 
 #[allow(clippy::too_many_arguments, clippy::type_complexity)]
@@ -311,8 +416,8 @@ pub fn snake_movement_control_system(
     mut _snake_reach_goal_event: EventWriter<SnakeReachGoalEvent>,
     mut commands: Commands,
     mut snake_moved_event: EventWriter<SnakeMovedEvent>,
-    mut selected_snake_query: Query<(Entity, &mut Snake), WithMovementControlSystemFilter>,
-    mut other_snakes_query: Query<(Entity, &mut Snake), Without<SelectedSnake>>,
+    selected_snakes_query: Query<(Entity, &SelectedSnake), WithMovementControlSystemFilter>,
+    mut snakes_query: Query<(Entity, &mut Snake)>,
     mut boxes_query: Query<(Entity, &mut GridEntity), (With<BoxComponent>, Without<FoodComponent>)>,
     foods_query: Query<&GridEntity, (With<FoodComponent>, Without<BoxComponent>)>,
     goal_query: Query<
@@ -325,157 +430,176 @@ pub fn snake_movement_control_system(
         ),
     >,
 ) {
-    let Ok((snake_entity, mut snake)) = selected_snake_query.get_single_mut() else {
-        return;
-    };
+    // Each player moves at most once per frame, moves of both players go to the same history.
+    let mut moved_players = vec![];
 
-    let Some(MoveCommandEvent(direction)) = move_command_event.iter().next() else {
-        return;
-    };
+    for MoveCommandEvent(direction, player) in move_command_event.iter() {
+        if moved_players.contains(player) {
+            continue;
+        }
 
-    if *direction == -snake.head_direction() {
-        return;
-    }
+        let Some((snake_entity, _)) = selected_snakes_query
+            .iter()
+            .find(|(_, selected_snake)| selected_snake.0 == *player)
+        else {
+            continue;
+        };
+
+        let mut movable_registry = MovableRegistry::new(&mut snakes_query, &mut boxes_query);
+
+        let Some(mut snake) = movable_registry.take_snake(snake_entity) else {
+            continue;
+        };
 
-    let mut movable_registry = MovableRegistry::new(&mut other_snakes_query, &mut boxes_query);
+        if *direction == -snake.head_direction() || !level_instance.allows_direction(*direction) {
+            continue;
+        }
 
-    // We try to move with the input direction, if not possible try to go up.
-    let directions = vec![*direction, IVec3::Y];
+        // We try to move with the input direction, if not possible try to go up.
+        let gravity = level_instance.gravity();
+        let directions: Vec<IVec3> = [*direction, -gravity]
+            .into_iter()
+            .filter(|direction| level_instance.allows_direction(*direction))
+            .collect();
 
-    let move_forward_or_up = 'choose_direction: {
-        for direction in directions {
-            let new_position = snake.head_position() + direction;
+        let move_forward_or_up = 'choose_direction: {
+            for direction in directions {
+                let new_position = snake.head_position() + direction;
 
-            // Check that we have enough parts to go up.
-            let is_goal = if let Ok(goal) = goal_query.get_single() {
-                goal.position == new_position
-            } else {
-                false
-            };
+                // Check that we have enough parts to go up.
+                let is_goal = if let Ok(goal) = goal_query.get_single() {
+                    goal.position == new_position
+                } else {
+                    false
+                };
 
-            if direction == IVec3::Y
-                && snake.is_standing()
-                && !level_instance.is_food(new_position)
-                && !is_goal
-            {
-                commands.entity(snake_entity).insert(GravityFall {
-                    velocity: constants.jump_velocity,
-                    relative_z: 0.0,
-                    grid_distance: 0,
-                });
-                break 'choose_direction None;
-            }
+                if direction == -gravity
+                    && snake.is_standing(gravity)
+                    && !level_instance.is_food(new_position)
+                    && !is_goal
+                {
+                    commands.entity(snake_entity).insert(GravityFall {
+                        velocity: constants.jump_velocity,
+                        relative_z: 0.0,
+                        grid_distance: 0,
+                    });
+                    break 'choose_direction None;
+                }
 
-            if level_instance.is_entity(new_position, snake_entity) {
-                continue;
-            }
+                if level_instance.is_entity(new_position, snake_entity) {
+                    continue;
+                }
 
-            // Find if there is a movable entity in the way.
-            let movable_entity = level_instance.is_movable(new_position);
-            let movable =
-                movable_entity.map(|entity| (entity.entity, movable_registry.get(&entity)));
+                // Find if there is a movable entity in the way.
+                let movable_entity = level_instance.is_movable(new_position);
+                let movable =
+                    movable_entity.map(|entity| (entity.entity, movable_registry.get(&entity)));
 
-            // Check if we can move forward.
-            if snake_can_move_forward(&level_instance, &snake, &movable, direction) {
-                break 'choose_direction Some((direction, new_position, movable_entity));
+                // Check if we can move forward.
+                if snake_can_move_forward(&level_instance, &snake, &movable, direction) {
+                    break 'choose_direction Some((direction, new_position, movable_entity));
+                }
             }
+            None
+        };
+
+        let Some((direction, new_position, movable_entity)) = move_forward_or_up else {
+            continue;
+        };
+
+        // Any food?
+        let food = foods_query
+            .iter()
+            .find(|food| food.position == new_position);
+
+        // Finaly move the snake forward and commit the state.
+        let mut snake_commands = SnakeCommands::new(&mut level_instance, &mut snake_history);
+
+        let movable = movable_entity.map(|entity| (entity, movable_registry.get_mut(&entity)));
+
+        snake_commands
+            .player_move(*player, snake.as_mut(), snake_entity, direction)
+            .pushing_entity(movable)
+            .eating_food(food)
+            .execute();
+
+        // if let Ok(goal) = goal_query.get_single() {
+        //     if snake.head_position() == goal.0 {
+        //         snake_reach_goal_event.send(SnakeReachGoalEvent(snake_entity));
+        //     }
+        // }
+
+        snake_moved_event.send(SnakeMovedEvent(snake_entity));
+
+        // Smooth move animation starts.
+        commands.entity(snake_entity).insert(MoveCommand {
+            velocity: constants.move_velocity,
+            lerp_time: 0.0,
+        });
+
+        if let Some(other_snake_entity) = movable_entity {
+            commands
+                .entity(other_snake_entity.entity)
+                .insert(PushedAnim {
+                    direction: direction.as_vec3(),
+                    velocity: constants.move_velocity,
+                    lerp_time: 0.0,
+                });
         }
-        None
-    };
 
-    let Some((direction, new_position, movable_entity)) = move_forward_or_up else {
-        return;
-    };
+        audio
+            .play(assets.move_effect.clone())
+            .with_playback_rate(1.0 + rand::thread_rng().gen_range(-0.05..0.1))
+            .with_volume(2.0);
 
-    // Any food?
-    let food = foods_query
-        .iter()
-        .find(|food| food.position == new_position);
-
-    // Finaly move the snake forward and commit the state.
-    let mut snake_commands = SnakeCommands::new(&mut level_instance, &mut snake_history);
-
-    let movable = movable_entity.map(|entity| (entity, movable_registry.get_mut(&entity)));
-
-    snake_commands
-        .player_move(snake.as_mut(), snake_entity, direction)
-        .pushing_entity(movable)
-        .eating_food(food)
-        .execute();
-
-    // if let Ok(goal) = goal_query.get_single() {
-    //     if snake.head_position() == goal.0 {
-    //         snake_reach_goal_event.send(SnakeReachGoalEvent(snake_entity));
-    //     }
-    // }
-
-    snake_moved_event.send(SnakeMovedEvent);
-
-    // Smooth move animation starts.
-    commands.entity(snake_entity).insert(MoveCommand {
-        velocity: constants.move_velocity,
-        lerp_time: 0.0,
-    });
-
-    if let Some(other_snake_entity) = movable_entity {
-        commands
-            .entity(other_snake_entity.entity)
-            .insert(PushedAnim {
-                direction: direction.as_vec3(),
-                velocity: constants.move_velocity,
-                lerp_time: 0.0,
-            });
+        moved_players.push(*player);
     }
-
-    audio
-        .play(assets.move_effect.clone())
-        .with_playback_rate(1.0 + rand::thread_rng().gen_range(-0.05..0.1))
-        .with_volume(2.0);
 }
 
 pub fn grow_snake_on_move_system(
     mut snake_moved_event: EventReader<SnakeMovedEvent>,
     mut meshes: ResMut<bevy::asset::Assets<Mesh>>,
     mut materials: ResMut<bevy::asset::Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
     mut commands: Commands,
-    snake_query: Query<(Entity, &Snake), With<SelectedSnake>>,
+    snake_query: Query<&Snake>,
     foods_query: Query<(Entity, &GridEntity), With<FoodComponent>>,
 ) {
-    if snake_moved_event.iter().next().is_none() {
-        return;
-    }
-
-    let Ok((snake_entity, snake)) = snake_query.get_single() else {
-        return;
-    };
-
-    for (food_entity, food) in &foods_query {
-        if food.position != snake.head_position() {
+    for SnakeMovedEvent(snake_entity) in snake_moved_event.iter() {
+        let snake_entity = *snake_entity;
+        let Ok(snake) = snake_query.get(snake_entity) else {
             continue;
-        }
+        };
 
-        commands.entity(food_entity).despawn();
+        for (food_entity, food) in &foods_query {
+            if food.position != snake.head_position() {
+                continue;
+            }
 
-        let grow_tween = Tween::new(
-            EaseFunction::QuadraticInOut,
-            std::time::Duration::from_secs_f32(0.2),
-            GrowPartLens,
-        );
+            commands.entity(food_entity).despawn();
 
-        let mut part_builder = MaterialMeshBuilder {
-            meshes: meshes.as_mut(),
-            materials: materials.as_mut(),
-        };
+            let grow_tween = Tween::new(
+                EaseFunction::QuadraticInOut,
+                std::time::Duration::from_secs_f32(0.2),
+                GrowPartLens,
+            );
 
-        commands.entity(snake_entity).with_children(|parent| {
-            parent
-                .spawn(part_builder.build_part(
-                    snake.tail_position(),
-                    snake.index(),
-                    snake.len() - 1,
-                ))
-                .insert((Animator::new(grow_tween), PartGrowAnim { grow_factor: 0.0 }));
-        });
+            let mut part_builder = MaterialMeshBuilder {
+                meshes: meshes.as_mut(),
+                materials: materials.as_mut(),
+                cache: cache.as_mut(),
+            };
+
+            commands.entity(snake_entity).with_children(|parent| {
+                parent
+                    .spawn(part_builder.build_part(
+                        snake.tail_position(),
+                        snake.index(),
+                        snake.len() - 1,
+                    ))
+                    .insert((Animator::new(grow_tween), PartGrowAnim { grow_factor: 0.0 }));
+            });
+        }
     }
 }
 
@@ -485,7 +609,7 @@ pub fn activate_trigger_on_move_system(
     undo_event: EventReader<UndoEvent>,
     mut commands: Commands,
     mut triggers: Query<
-        (Entity, &mut Transform, &GridEntity, Option<&Active>),
+        (Entity, &mut Transform, &GridEntity, Option<&PressedTrigger>),
         With<TriggerComponent>,
     >,
 ) {
@@ -493,15 +617,101 @@ pub fn activate_trigger_on_move_system(
         return;
     }
 
-    for (trigger_entity, mut transform, trigger, active) in &mut triggers {
+    for (trigger_entity, mut transform, trigger, pressed) in &mut triggers {
         let has_load = level_instance.is_movable(trigger.position).is_some();
 
-        if has_load && active.is_none() {
-            commands.entity(trigger_entity).insert(Active);
-            transform.translation -= 0.18 * Vec3::Y;
-        } else if !has_load && active.is_some() {
-            transform.translation += 0.18 * Vec3::Y;
-            commands.entity(trigger_entity).remove::<Active>();
+        match pressed {
+            None if has_load => {
+                let offset = 0.18 * level_instance.gravity().as_vec3();
+                transform.translation += offset;
+                commands
+                    .entity(trigger_entity)
+                    .insert((Active, PressedTrigger(offset)));
+            }
+            Some(PressedTrigger(offset)) if !has_load => {
+                transform.translation -= *offset;
+                commands
+                    .entity(trigger_entity)
+                    .remove::<(Active, PressedTrigger)>();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn flip_gravity_on_switch_system(
+    mut snake_moved_event: EventReader<SnakeMovedEvent>,
+    mut level_instance: ResMut<LevelInstance>,
+    mut snake_history: ResMut<SnakeHistory>,
+    snakes: Query<&Snake>,
+    switches: Query<&GridEntity, With<GravitySwitchComponent>>,
+) {
+    for SnakeMovedEvent(snake_entity) in snake_moved_event.iter() {
+        let Ok(snake) = snakes.get(*snake_entity) else {
+            continue;
+        };
+
+        if !switches
+            .iter()
+            .any(|switch| switch.position == snake.head_position())
+        {
+            continue;
+        }
+
+        SnakeCommands::new(&mut level_instance, &mut snake_history).flip_gravity(*snake_entity);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn crumble_breakable_walls_system(
+    mut snake_moved_event: EventReader<SnakeMovedEvent>,
+    mut movable_fell_event: EventReader<MovableFellEvent>,
+    mut level_instance: ResMut<LevelInstance>,
+    mut snake_history: ResMut<SnakeHistory>,
+    mut commands: Commands,
+    mut walls: Query<(
+        Entity,
+        &GridEntity,
+        &mut BreakableWallComponent,
+        Option<&EntityProperties>,
+    )>,
+) {
+    // A move is a turn for every wall, a fall step only for the wall the fallen movable loads.
+    let snake_moved = snake_moved_event.iter().count() > 0;
+    let fallen: HashSet<Entity> = movable_fell_event.iter().map(|event| event.0).collect();
+    if !snake_moved && fallen.is_empty() {
+        return;
+    }
+
+    for (wall_entity, wall, mut state, properties) in &mut walls {
+        let max_load_turns = properties
+            .and_then(|properties| properties.int(MAX_LOAD_TURNS_PROPERTY))
+            .map_or(BREAKABLE_WALL_MAX_LOAD_TURNS, |turns| turns.max(1) as u32);
+
+        let load = level_instance.is_movable(wall.position - level_instance.gravity());
+        let has_load = load.is_some();
+        let turn = snake_moved || load.map_or(false, |load| fallen.contains(&load.entity));
+
+        let new_state = if has_load {
+            BreakableWallComponent {
+                turns_under_load: state.turns_under_load + u32::from(turn),
+                loaded: true,
+            }
+        } else {
+            *state
+        };
+
+        // The wall breaks when the load left, or when it was loaded for too long.
+        let breaks = (state.loaded && !has_load) || new_state.turns_under_load >= max_load_turns;
+
+        let mut snake_commands = SnakeCommands::new(&mut level_instance, &mut snake_history);
+
+        if breaks {
+            snake_commands.break_wall(wall_entity, wall.position, *state);
+            commands.entity(wall_entity).despawn_recursive();
+        } else if new_state != *state {
+            snake_commands.load_wall(wall_entity, wall.position, *state);
+            *state = new_state;
         }
     }
 }
@@ -514,6 +724,7 @@ pub fn gravity_system<MovableType, Filter>(
     mut snake_history: ResMut<SnakeHistory>,
     mut _trigger_undo_event: EventWriter<UndoEvent>,
     mut snake_reach_goal_event: EventReader<SnakeReachGoalEvent>,
+    mut movable_fell_event: EventWriter<MovableFellEvent>,
     mut commands: Commands,
     mut query: Query<(Entity, &mut MovableType, Option<&mut GravityFall>), Filter>,
 ) where
@@ -541,7 +752,8 @@ pub fn gravity_system<MovableType, Filter>(
                     LevelGridEntity::new(movable_entity, movable.entity_type()),
                 );
 
-                movable.translate(IVec3::NEG_Y);
+                movable.translate(level.gravity());
+                movable_fell_event.send(MovableFellEvent(movable_entity));
 
                 commands.entity(movable_entity).insert(GravityFall {
                     velocity: 0.0,
@@ -578,7 +790,8 @@ pub fn gravity_system<MovableType, Filter>(
                     gravity_fall.relative_z = 1.0;
                     gravity_fall.grid_distance += 1;
 
-                    movable.translate(IVec3::NEG_Y);
+                    movable.translate(level.gravity());
+                    movable_fell_event.send(MovableFellEvent(movable_entity));
                 } else {
                     // ..or stop falling animation.
                     commands.entity(movable_entity).remove::<GravityFall>();
@@ -593,6 +806,7 @@ pub fn gravity_system<MovableType, Filter>(
                         movable.as_ref(),
                         LevelGridEntity::new(movable_entity, movable.entity_type()),
                     );
+                    movable_fell_event.send(MovableFellEvent(movable_entity));
                 }
             }
         }
@@ -628,6 +842,7 @@ pub fn snake_push_anim_system(
 #[allow(clippy::too_many_arguments)]
 pub fn snake_exit_level_anim_system(
     constants: Res<GameConstants>,
+    level_instance: Res<LevelInstance>,
     mut commands: Commands,
     mut event_despawn_snake_parts: EventWriter<DespawnSnakePartEvent>,
     mut event_snake_exited_level: EventWriter<SnakeExitedLevelEvent>,
@@ -686,8 +901,7 @@ pub fn snake_exit_level_anim_system(
                 velocity: 1.5 * constants.move_velocity,
                 lerp_time: 0.0,
             });
-            let direction = IVec3::NEG_Y;
-            snake.move_forward(direction);
+            snake.move_forward(level_instance.gravity());
         }
     }
 }
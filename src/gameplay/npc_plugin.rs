@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::IntoConditionalSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gameplay::commands::SnakeCommands,
+    gameplay::game_constants_plugin::GameConstants,
+    gameplay::level_entities::{BoxComponent, GridEntity},
+    gameplay::movement_plugin::{
+        snake_can_move_forward, GravityFall, MovableRegistry, MoveCommand, MovementStages,
+        PushedAnim, SnakeMovedEvent,
+    },
+    gameplay::snake_plugin::{Active, Snake, SnakeTemplate},
+    gameplay::undo::SnakeHistory,
+    level::level_instance::LevelInstance,
+    GameState,
+};
+
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            npc_snakes_move_system
+                .run_in_state(GameState::Game)
+                .run_if_resource_exists::<LevelInstance>()
+                .label(MovementStages::NpcMovement)
+                .after(MovementStages::SnakeMovement)
+                .before(MovementStages::SnakeGrow)
+                .before(MovementStages::GravitySwitch),
+        );
+    }
+}
+
+/// How a non-player snake chooses where to go, each behaviour is deterministic.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum NpcBehaviour {
+    /// Follow a closed path of cells, the head goes to the cell following the one it is on.
+    /// Cells lower along gravity are only reached by falling.
+    Patrol(Vec<IVec3>),
+    /// Keep a wall on the left, turning right when blocked.
+    FollowWall,
+    /// Get closer to the head of the player snake that just moved.
+    ChasePlayer,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NpcSnakeTemplate {
+    pub parts: SnakeTemplate,
+    pub behaviour: NpcBehaviour,
+}
+
+/// A snake that is not controlled by the player, it takes one step after every player move.
+#[derive(Component, Clone)]
+pub struct NpcSnake {
+    pub behaviour: NpcBehaviour,
+}
+
+/// The four directions orthogonal to gravity, in a fixed order.
+fn horizontal_directions(gravity: IVec3) -> Vec<IVec3> {
+    [
+        IVec3::X,
+        IVec3::NEG_X,
+        IVec3::Z,
+        IVec3::NEG_Z,
+        IVec3::Y,
+        IVec3::NEG_Y,
+    ]
+    .into_iter()
+    .filter(|direction| direction.dot(gravity) == 0)
+    .collect()
+}
+
+/// Step along the axis with the largest distance to the target, never along gravity. Like the
+/// player snakes, npcs only get lower by falling and can't climb into the air.
+fn step_towards(from: IVec3, to: IVec3, gravity: IVec3) -> Option<IVec3> {
+    let delta = to - from;
+    let delta = delta - gravity * delta.dot(gravity);
+    if delta == IVec3::ZERO {
+        return None;
+    }
+
+    let abs = delta.abs();
+    let step = if abs.x >= abs.y && abs.x >= abs.z {
+        IVec3::new(delta.x.signum(), 0, 0)
+    } else if abs.y >= abs.z {
+        IVec3::new(0, delta.y.signum(), 0)
+    } else {
+        IVec3::new(0, 0, delta.z.signum())
+    };
+
+    Some(step)
+}
+
+impl NpcBehaviour {
+    /// The directions to try, in order of preference.
+    pub fn candidate_directions(
+        &self,
+        snake: &Snake,
+        gravity: IVec3,
+        player_head: Option<IVec3>,
+    ) -> Vec<IVec3> {
+        let head = snake.head_position();
+
+        match self {
+            NpcBehaviour::Patrol(path) => {
+                let target = path
+                    .iter()
+                    .position(|cell| *cell == head)
+                    .map(|index| path[(index + 1) % path.len()])
+                    .or_else(|| path.first().copied());
+
+                target
+                    .and_then(|target| step_towards(head, target, gravity))
+                    .into_iter()
+                    .collect()
+            }
+            NpcBehaviour::FollowWall => {
+                let up = -gravity;
+                let forward = if snake.head_direction().dot(gravity) == 0 {
+                    snake.head_direction()
+                } else {
+                    horizontal_directions(gravity)[0]
+                };
+                let left = up.cross(forward);
+
+                vec![left, forward, -left]
+            }
+            NpcBehaviour::ChasePlayer => {
+                let Some(player_head) = player_head else {
+                    return vec![];
+                };
+
+                let mut directions = horizontal_directions(gravity);
+                directions.sort_by_key(|direction| {
+                    let distance = (head + *direction - player_head).abs();
+                    distance.x + distance.y + distance.z
+                });
+                directions
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn npc_snakes_move_system(
+    constants: Res<GameConstants>,
+    mut snake_moved_event: EventReader<SnakeMovedEvent>,
+    mut level_instance: ResMut<LevelInstance>,
+    mut snake_history: ResMut<SnakeHistory>,
+    mut commands: Commands,
+    mut npc_query: Query<(Entity, &NpcSnake, &mut Snake), (With<Active>, Without<GravityFall>)>,
+    mut other_snakes_query: Query<(Entity, &mut Snake), Without<NpcSnake>>,
+    mut boxes_query: Query<(Entity, &mut GridEntity), With<BoxComponent>>,
+) {
+    let Some(SnakeMovedEvent(player_entity)) = snake_moved_event.iter().last() else {
+        return;
+    };
+
+    let player_head = other_snakes_query
+        .get(*player_entity)
+        .ok()
+        .map(|(_, snake)| snake.head_position());
+
+    // Npcs move one after the other, in a stable order.
+    let mut npcs: Vec<_> = npc_query.iter_mut().collect();
+    npcs.sort_by_key(|(_, _, snake)| snake.index());
+
+    for (npc_entity, npc, mut snake) in npcs {
+        let mut movable_registry = MovableRegistry::new(&mut other_snakes_query, &mut boxes_query);
+        let gravity = level_instance.gravity();
+
+        let direction = npc
+            .behaviour
+            .candidate_directions(&snake, gravity, player_head)
+            .into_iter()
+            .filter(|direction| level_instance.allows_direction(*direction))
+            .find(|direction| {
+                let new_position = snake.head_position() + *direction;
+
+                // Npcs don't eat and never leave the level.
+                if level_instance.is_entity(new_position, npc_entity)
+                    || level_instance.is_food(new_position)
+                    || level_instance.is_goal(new_position)
+                {
+                    return false;
+                }
+
+                let movable_entity = level_instance.is_movable(new_position);
+                if let Some(movable_entity) = movable_entity {
+                    // Other npcs can't be pushed.
+                    if !movable_registry.contains(&movable_entity) {
+                        return false;
+                    }
+                }
+
+                let movable =
+                    movable_entity.map(|entity| (entity.entity, movable_registry.get(&entity)));
+
+                snake_can_move_forward(&level_instance, &snake, &movable, *direction)
+            });
+
+        let Some(direction) = direction else {
+            continue;
+        };
+
+        let movable_entity = level_instance.is_movable(snake.head_position() + direction);
+        let movable = movable_entity.map(|entity| (entity, movable_registry.get_mut(&entity)));
+
+        SnakeCommands::new(&mut level_instance, &mut snake_history)
+            .npc_move(snake.as_mut(), npc_entity, direction)
+            .pushing_entity(movable)
+            .execute();
+
+        commands
+            .entity(npc_entity)
+            .insert(MoveCommand::new(constants.move_velocity));
+
+        if let Some(pushed_entity) = movable_entity {
+            commands
+                .entity(pushed_entity.entity)
+                .insert(PushedAnim::new(
+                    direction.as_vec3(),
+                    constants.move_velocity,
+                ));
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet, IntoConditionalSystem};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    despawn_with_system,
+    gameplay::level_plugin::LevelCompletedEvent,
+    gameplay::undo::SnakeHistory,
+    level::level_template::{LevelTemplate, LoadedLevel},
+    menus::FONT,
+    GameState,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const PROGRESS_FILE: &str = "progress.ron";
+
+const COMPLETION_TEXT_SECONDS: f32 = 3.0;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Progress::load())
+            .add_enter_system(GameState::Game, spawn_move_counter_system)
+            .add_exit_system(GameState::Game, despawn_with_system::<ScoreUi>)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Game)
+                    .with_system(update_move_counter_system)
+                    .with_system(hide_completion_text_system)
+                    .into(),
+            )
+            // After the update stage, so the level completion is recorded before the game
+            // leaves for the menus.
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                record_level_completion_system.run_in_state(GameState::Game),
+            );
+    }
+}
+
+#[derive(Component)]
+struct ScoreUi;
+
+#[derive(Component)]
+struct MoveCounterText;
+
+#[derive(Component)]
+struct CompletionText(Timer);
+
+/// The best result of the player on a level.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct LevelProgress {
+    pub best_moves: u32,
+    pub stars: u8,
+}
+
+/// The progress of the player, keyed by level asset path.
+#[derive(Resource, Deserialize, Serialize, Default, Clone, Debug)]
+pub struct Progress {
+    pub levels: BTreeMap<String, LevelProgress>,
+}
+
+impl Progress {
+    pub fn stars(&self, asset_path: &str) -> Option<u8> {
+        self.levels.get(asset_path).map(|level| level.stars)
+    }
+
+    /// Keep the best move count and rating for the level.
+    pub fn record(&mut self, asset_path: &str, moves: u32, stars: u8) {
+        let level = self
+            .levels
+            .entry(asset_path.to_owned())
+            .or_insert(LevelProgress {
+                best_moves: moves,
+                stars,
+            });
+
+        level.best_moves = level.best_moves.min(moves);
+        level.stars = level.stars.max(stars);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load() -> Self {
+        std::fs::read_to_string(PROGRESS_FILE)
+            .ok()
+            .and_then(|ron_string| ron::from_str(&ron_string).ok())
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load() -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self) {
+        let ron_string =
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap();
+
+        if let Err(error) = std::fs::write(PROGRESS_FILE, ron_string) {
+            error!("Failed to save progress: {}", error);
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self) {}
+}
+
+/// Three stars at par or better, two up to half more moves than par, one otherwise.
+/// Levels without a par always give three stars.
+pub fn star_rating(moves: u32, par: Option<u32>) -> u8 {
+    let Some(par) = par else {
+        return 3;
+    };
+
+    if moves <= par {
+        3
+    } else if 2 * moves <= 3 * par {
+        2
+    } else {
+        1
+    }
+}
+
+pub fn text_style(assets: &AssetServer) -> TextStyle {
+    TextStyle {
+        font: assets.load(FONT),
+        font_size: 24.0,
+        color: Color::BLACK,
+    }
+}
+
+fn spawn_move_counter_system(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section("", text_style(&assets)).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(12.0),
+                top: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        }),
+        MoveCounterText,
+        ScoreUi,
+    ));
+}
+
+fn update_move_counter_system(
+    history: Option<Res<SnakeHistory>>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    level_templates: Res<Assets<LevelTemplate>>,
+    mut text_query: Query<&mut Text, With<MoveCounterText>>,
+) {
+    let Some(history) = history else {
+        return;
+    };
+
+    if !history.is_changed() {
+        return;
+    }
+
+    let par = loaded_level
+        .and_then(|level| level_templates.get(&level.0))
+        .and_then(|template| template.par);
+
+    let counter = match par {
+        Some(par) => format!("Moves: {} / Par: {}", history.player_move_count(), par),
+        None => format!("Moves: {}", history.player_move_count()),
+    };
+
+    for mut text in &mut text_query {
+        text.sections[0].value = counter.clone();
+    }
+}
+
+fn record_level_completion_system(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut progress: ResMut<Progress>,
+    mut level_completed_event: EventReader<LevelCompletedEvent>,
+) {
+    for event in level_completed_event.iter() {
+        let stars = star_rating(event.moves, event.par);
+
+        progress.record(&event.asset_path, event.moves, stars);
+        progress.save();
+
+        commands.spawn((
+            TextBundle::from_section(
+                format!("Level complete in {} moves: {}/3 stars", event.moves, stars),
+                text_style(&assets),
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(12.0),
+                    top: Val::Px(40.0),
+                    ..default()
+                },
+                ..default()
+            }),
+            CompletionText(Timer::from_seconds(
+                COMPLETION_TEXT_SECONDS,
+                TimerMode::Once,
+            )),
+            ScoreUi,
+        ));
+    }
+}
+
+fn hide_completion_text_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut text_query: Query<(Entity, &mut CompletionText)>,
+) {
+    for (entity, mut completion_text) in &mut text_query {
+        if completion_text.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
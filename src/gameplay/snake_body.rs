@@ -0,0 +1,439 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, primitives::Aabb, render_resource::PrimitiveTopology},
+    utils::HashSet,
+};
+use iyes_loopless::state::CurrentState;
+
+use crate::GameState;
+
+use super::{
+    movement_plugin::PartGrowAnim,
+    snake_plugin::{
+        MaterialMeshBuilder, MeshMaterialCache, PartClipper, Snake, SnakePart, SnakeStages,
+    },
+    theme_plugin::{snake_stripe, ThemeColor},
+};
+
+const BODY_SIDES: usize = 12;
+
+/// Rings of each of the half spheres closing the head and the tail.
+const CAP_RINGS: usize = 4;
+
+/// Samples of the curve replacing each turn of the body.
+const CORNER_STEPS: usize = 6;
+
+/// How far from the cell of a turn the curve starts, half a cell joins the turns next to it.
+const CORNER_RADIUS: f32 = 0.5;
+
+/// The largest distance between two rings, so the radius changes smoothly along straight parts.
+const RING_SPACING: f32 = 0.25;
+
+const HEAD_RADIUS: f32 = 0.42;
+const BODY_RADIUS: f32 = 0.35;
+const TAIL_RADIUS: f32 = 0.18;
+
+/// The distance over which the head narrows to the body.
+const HEAD_LENGTH: f32 = 0.75;
+
+/// The distance over which the body narrows to the tail.
+const TAIL_LENGTH: f32 = 1.5;
+
+pub struct SnakeBodyPlugin;
+
+impl Plugin for SnakeBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_snake_body_system)
+            .add_system(show_parts_in_editor_system)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_snake_body_system.after(SnakeStages::UpdateTransforms),
+            );
+    }
+}
+
+/// The mesh drawing a snake as one tube going through its parts, spawned as a child of the snake.
+#[derive(Component)]
+pub struct SnakeBody {
+    pub entity: Entity,
+    pub mesh: Handle<Mesh>,
+}
+
+/// A point of the center line of the body, `part` is the index of the parts it lies between.
+#[derive(Clone, Copy)]
+struct BodyPoint {
+    position: Vec3,
+    part: f32,
+}
+
+impl BodyPoint {
+    fn lerp(self, other: BodyPoint, t: f32) -> BodyPoint {
+        BodyPoint {
+            position: self.position.lerp(other.position, t),
+            part: self.part + (other.part - self.part) * t,
+        }
+    }
+}
+
+/// A circle of vertices around the center line, the rings of the caps shrink to a point.
+#[derive(Clone, Copy)]
+struct Ring {
+    center: Vec3,
+    tangent: Vec3,
+    normal: Vec3,
+    binormal: Vec3,
+    radius: f32,
+    /// The part of the vertex normals along the tangent, non zero on the caps only.
+    slope: f32,
+    color: [f32; 4],
+}
+
+impl Ring {
+    /// A ring of the half sphere closing the end of the body, `angle` goes from the end ring to
+    /// the tip and `side` is -1 at the head and 1 at the tail.
+    fn cap(self, angle: f32, side: f32) -> Ring {
+        Ring {
+            center: self.center + self.tangent * side * self.radius * angle.sin(),
+            radius: self.radius * angle.cos(),
+            slope: side * angle.sin(),
+            ..self
+        }
+    }
+}
+
+fn spawn_snake_body_system(
+    mut commands: Commands,
+    snakes: Query<Entity, (With<Snake>, Without<SnakeBody>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
+) {
+    for snake_entity in &snakes {
+        let mut builder = MaterialMeshBuilder {
+            meshes: meshes.as_mut(),
+            materials: materials.as_mut(),
+            cache: cache.as_mut(),
+        };
+        let material = builder.snake_body_material();
+        let mesh = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
+
+        let entity = commands
+            .spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material,
+                    visibility: Visibility::INVISIBLE,
+                    ..default()
+                },
+                // Kept up to date with the mesh, the bounds aren't computed for changed meshes.
+                Aabb::default(),
+                Name::new("Snake Body"),
+            ))
+            .id();
+
+        commands
+            .entity(snake_entity)
+            .add_child(entity)
+            .insert(SnakeBody { entity, mesh });
+    }
+}
+
+/// Rebuild the body meshes of the snakes whose parts changed, once the parts are placed for this
+/// frame. The editor draws the parts instead.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_snake_body_system(
+    snakes: Query<(
+        Entity,
+        &Snake,
+        ChangeTrackers<Snake>,
+        &Transform,
+        ChangeTrackers<Transform>,
+        &SnakeBody,
+        &Children,
+        ChangeTrackers<Children>,
+    )>,
+    changed_parts: Query<
+        &Parent,
+        (
+            With<SnakePart>,
+            Or<(
+                Changed<Transform>,
+                Changed<PartGrowAnim>,
+                Changed<PartClipper>,
+            )>,
+        ),
+    >,
+    parents: Query<&Parent, With<SnakePart>>,
+    removed_grow_anims: RemovedComponents<PartGrowAnim>,
+    removed_clippers: RemovedComponents<PartClipper>,
+    parts: Query<(
+        &SnakePart,
+        &Transform,
+        Option<&PartGrowAnim>,
+        Option<&PartClipper>,
+    )>,
+    mut bodies: Query<(&mut Aabb, &mut Visibility)>,
+    cache: Res<MeshMaterialCache>,
+    state: Res<CurrentState<GameState>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut changed_snakes: HashSet<Entity> =
+        changed_parts.iter().map(|parent| parent.get()).collect();
+    changed_snakes.extend(
+        removed_grow_anims
+            .iter()
+            .chain(removed_clippers.iter())
+            .filter_map(|part| parents.get(part).ok())
+            .map(|parent| parent.get()),
+    );
+
+    // Theme changes recolor every body.
+    let rebuild_all = cache.is_changed() || state.is_changed();
+    let in_editor = state.0 == GameState::Editor;
+
+    for (
+        snake_entity,
+        snake,
+        snake_tracker,
+        snake_transform,
+        transform_tracker,
+        body,
+        children,
+        children_tracker,
+    ) in &snakes
+    {
+        let changed = rebuild_all
+            || changed_snakes.contains(&snake_entity)
+            || snake_tracker.is_changed()
+            || transform_tracker.is_changed()
+            || children_tracker.is_changed();
+        if !changed {
+            continue;
+        }
+
+        let mut snake_parts: Vec<_> = children
+            .iter()
+            .filter_map(|child| parts.get(*child).ok())
+            .collect();
+        snake_parts.sort_by_key(|(part, ..)| part.part_index);
+
+        let mut points: Vec<BodyPoint> = vec![];
+        let mut head_clipped = false;
+
+        for (part, transform, grow_anim, clipper) in snake_parts {
+            let mut position = transform.translation;
+
+            // Parts moving through the exit stop at it, as if the exit swallowed them.
+            if let Some(clipper) = clipper {
+                let clip_position = clipper.clip_position.as_vec3() - snake_transform.translation;
+                let direction = snake
+                    .parts()
+                    .get(part.part_index)
+                    .map_or(Vec3::ZERO, |(_, direction)| direction.as_vec3());
+                position -= direction * (position - clip_position).dot(direction).max(0.0);
+                head_clipped |= points.is_empty();
+            }
+
+            // Growing parts come out of the part before them.
+            if let (Some(grow_anim), Some(previous)) = (grow_anim, points.last()) {
+                position = previous.position.lerp(position, grow_anim.grow_factor);
+            }
+
+            let overlaps_previous = points.last().map_or(false, |previous| {
+                previous.position.distance(position) < 0.01
+            });
+            if !overlaps_previous {
+                points.push(BodyPoint {
+                    position,
+                    part: part.part_index as f32,
+                });
+            }
+        }
+
+        let Ok((mut aabb, mut visibility)) = bodies.get_mut(body.entity) else {
+            continue;
+        };
+        let Some(mesh) = meshes.get_mut(&body.mesh) else {
+            continue;
+        };
+
+        visibility.is_visible = !points.is_empty() && !in_editor;
+        if !visibility.is_visible {
+            continue;
+        }
+
+        let snake_index = snake.index() as usize;
+        let path = subdivide(&round_corners(&points));
+        build_body_mesh(mesh, &path, !head_clipped, |part| {
+            let color = cache.color(ThemeColor::Snake {
+                index: snake_index,
+                part: snake_stripe(snake_index, part),
+            });
+            color.as_linear_rgba_f32()
+        });
+
+        if let Some(bounds) = mesh.compute_aabb() {
+            *aabb = bounds;
+        }
+    }
+}
+
+/// The editor picks and highlights the parts, so they are drawn there instead of the body.
+fn show_parts_in_editor_system(
+    state: Res<CurrentState<GameState>>,
+    mut parts: Query<(&mut Visibility, ChangeTrackers<SnakePart>)>,
+) {
+    let in_editor = state.0 == GameState::Editor;
+
+    for (mut visibility, part_tracker) in &mut parts {
+        if (state.is_changed() || part_tracker.is_added()) && visibility.is_visible != in_editor {
+            visibility.is_visible = in_editor;
+        }
+    }
+}
+
+/// Replace the turns of the center line by quadratic Bézier curves around the cell of the turn.
+fn round_corners(points: &[BodyPoint]) -> Vec<BodyPoint> {
+    let mut path = vec![];
+
+    for (index, point) in points.iter().enumerate() {
+        if index == 0 || index == points.len() - 1 {
+            path.push(*point);
+            continue;
+        }
+
+        let previous = points[index - 1];
+        let next = points[index + 1];
+        let to_previous = previous.position - point.position;
+        let to_next = next.position - point.position;
+        if to_previous.normalize().dot(to_next.normalize()) < -0.999 {
+            path.push(*point);
+            continue;
+        }
+
+        let start = point.lerp(previous, (CORNER_RADIUS / to_previous.length()).min(0.5));
+        let end = point.lerp(next, (CORNER_RADIUS / to_next.length()).min(0.5));
+
+        for step in 0..=CORNER_STEPS {
+            let t = step as f32 / CORNER_STEPS as f32;
+            path.push(start.lerp(*point, t).lerp(point.lerp(end, t), t));
+        }
+    }
+
+    path
+}
+
+/// Add points along the long segments so there is a ring every [`RING_SPACING`].
+fn subdivide(path: &[BodyPoint]) -> Vec<BodyPoint> {
+    let mut points = vec![path[0]];
+
+    for segment in path.windows(2) {
+        let length = segment[0].position.distance(segment[1].position);
+        let steps = (length / RING_SPACING).ceil().max(1.0) as usize;
+
+        for step in 1..=steps {
+            points.push(segment[0].lerp(segment[1], step as f32 / steps as f32));
+        }
+    }
+
+    points
+}
+
+/// The radius of the body at `distance` from the head along a body of `length`.
+fn body_radius(distance: f32, length: f32) -> f32 {
+    let head = 1.0 - (distance / HEAD_LENGTH).clamp(0.0, 1.0);
+    let radius = BODY_RADIUS + (HEAD_RADIUS - BODY_RADIUS) * head * head * (3.0 - 2.0 * head);
+
+    let tail = ((length - distance) / TAIL_LENGTH).clamp(0.0, 1.0);
+    TAIL_RADIUS + (radius - TAIL_RADIUS) * tail.sqrt()
+}
+
+/// Sweep a circle along the path, with frames carried from ring to ring so the tube doesn't
+/// twist, and close it with half spheres. `color` gives the vertex color of a part index.
+fn build_body_mesh(
+    mesh: &mut Mesh,
+    path: &[BodyPoint],
+    head_cap: bool,
+    color: impl Fn(usize) -> [f32; 4],
+) {
+    let last = path.len() - 1;
+    let length: f32 = path
+        .windows(2)
+        .map(|segment| segment[0].position.distance(segment[1].position))
+        .sum();
+
+    let mut body_rings = Vec::with_capacity(path.len());
+    let mut distance = 0.0;
+    let mut tangent = Vec3::Z;
+    let mut normal = Vec3::X;
+
+    for (index, point) in path.iter().enumerate() {
+        if index > 0 {
+            distance += path[index - 1].position.distance(point.position);
+        }
+
+        let forward = path[(index + 1).min(last)].position - path[index.saturating_sub(1)].position;
+        tangent = forward.try_normalize().unwrap_or(tangent);
+        normal = (normal - tangent * normal.dot(tangent))
+            .try_normalize()
+            .unwrap_or_else(|| tangent.any_orthonormal_vector());
+
+        body_rings.push(Ring {
+            center: point.position,
+            tangent,
+            normal,
+            binormal: tangent.cross(normal),
+            radius: body_radius(distance, length),
+            slope: 0.0,
+            color: color(point.part.round() as usize),
+        });
+    }
+
+    let first_ring = body_rings[0];
+    let last_ring = body_rings[last];
+    let cap_angle = |step: usize| step as f32 / CAP_RINGS as f32 * FRAC_PI_2;
+
+    let head_rings = (1..=CAP_RINGS)
+        .rev()
+        .filter(|_| head_cap)
+        .map(|step| first_ring.cap(cap_angle(step), -1.0));
+    let tail_rings = (1..=CAP_RINGS).map(|step| last_ring.cap(cap_angle(step), 1.0));
+    let rings: Vec<Ring> = head_rings.chain(body_rings).chain(tail_rings).collect();
+
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(rings.len() * BODY_SIDES);
+    let mut normals: Vec<[f32; 3]> = Vec::with_capacity(rings.len() * BODY_SIDES);
+    let mut colors: Vec<[f32; 4]> = Vec::with_capacity(rings.len() * BODY_SIDES);
+    let mut indices: Vec<u32> = vec![];
+
+    for ring in &rings {
+        let radial_normal = (1.0 - ring.slope * ring.slope).sqrt();
+
+        for side in 0..BODY_SIDES {
+            let angle = side as f32 / BODY_SIDES as f32 * TAU;
+            let radial = ring.normal * angle.cos() + ring.binormal * angle.sin();
+
+            positions.push((ring.center + radial * ring.radius).to_array());
+            normals.push((radial * radial_normal + ring.tangent * ring.slope).to_array());
+            colors.push(ring.color);
+        }
+    }
+
+    for ring in 0..rings.len() - 1 {
+        for side in 0..BODY_SIDES {
+            let next_side = (side + 1) % BODY_SIDES;
+            let a = (ring * BODY_SIDES + side) as u32;
+            let a_next = (ring * BODY_SIDES + next_side) as u32;
+            let b = ((ring + 1) * BODY_SIDES + side) as u32;
+            let b_next = ((ring + 1) * BODY_SIDES + next_side) as u32;
+
+            indices.extend([a, a_next, b_next, a, b_next, b]);
+        }
+    }
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+}
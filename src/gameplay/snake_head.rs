@@ -0,0 +1,469 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    transform::TransformSystem,
+};
+use iyes_loopless::prelude::ConditionSet;
+use rand::prelude::*;
+
+use crate::{level::level_instance::LevelInstance, GameState};
+
+use super::{
+    movement_plugin::{GravityFall, MovementStages, SnakeReachGoalEvent},
+    snake_plugin::{
+        MaterialMeshBuilder, MeshMaterialCache, MeshShape, PartClipper, Snake, SnakePart,
+        SnakeStages,
+    },
+    theme_plugin::ThemeColor,
+};
+
+/// The eyes are mirrored on the x axis, the head looks toward -z.
+const EYE_POSITION: Vec3 = Vec3::new(0.17, 0.08, -0.34);
+const EYE_SCALE: Vec3 = Vec3::new(0.11, 0.11, 0.066);
+const PUPIL_OFFSET: Vec3 = Vec3::new(0.0, 0.0, -0.05);
+const EYELID_SCALE: Vec3 = Vec3::new(0.12, 0.12, 0.085);
+const MOUTH_POSITION: Vec3 = Vec3::new(0.0, -0.17, -0.36);
+
+/// How fast the head turns and the face changes, higher is faster.
+const TURN_SPEED: f32 = 12.0;
+const POSE_SPEED: f32 = 15.0;
+
+const BLINK_SECONDS: f32 = 0.15;
+const HAPPY_SECONDS: f32 = 2.0;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+pub struct SnakeHeadPlugin;
+
+impl Plugin for SnakeHeadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_snake_head_system)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Game)
+                    .run_if_resource_exists::<LevelInstance>()
+                    .after(MovementStages::SmoothMovement)
+                    .with_system(update_head_expression_system)
+                    .into(),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                animate_snake_head_system
+                    .after(SnakeStages::UpdateTransforms)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// What the face of a snake shows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Expression {
+    Idle,
+    /// The mouth opens when food is next to the head.
+    Eating,
+    /// While the snake falls.
+    Surprised,
+    /// After the snake reached the goal.
+    Happy,
+}
+
+/// How open the features of the face are, from 0 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct FacePose {
+    mouth_open: f32,
+    mouth_width: f32,
+    eyelids_closed: f32,
+    pupil_width: f32,
+}
+
+impl Expression {
+    fn pose(self) -> FacePose {
+        let (mouth_open, mouth_width, eyelids_closed, pupil_width) = match self {
+            Expression::Idle => (0.0, 1.0, 0.0, 0.45),
+            Expression::Eating => (1.0, 1.0, 0.0, 0.7),
+            Expression::Surprised => (0.6, 0.6, 0.0, 1.0),
+            Expression::Happy => (0.4, 1.4, 0.6, 0.7),
+        };
+
+        FacePose {
+            mouth_open,
+            mouth_width,
+            eyelids_closed,
+            pupil_width,
+        }
+    }
+}
+
+impl FacePose {
+    fn lerp(self, other: FacePose, t: f32) -> FacePose {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+        FacePose {
+            mouth_open: lerp(self.mouth_open, other.mouth_open),
+            mouth_width: lerp(self.mouth_width, other.mouth_width),
+            eyelids_closed: lerp(self.eyelids_closed, other.eyelids_closed),
+            pupil_width: lerp(self.pupil_width, other.pupil_width),
+        }
+    }
+}
+
+/// The animation state of the head of a snake, the expression is picked every frame from what
+/// happens to the snake and the head eases toward it.
+#[derive(Component)]
+pub struct HeadAnimation {
+    pub expression: Expression,
+    facing: Quat,
+    pose: FacePose,
+    happy_seconds: f32,
+    /// Counts down to the next blink, then the time left of the blink.
+    next_blink_seconds: f32,
+    blink_seconds: f32,
+}
+
+impl HeadAnimation {
+    fn new(facing: Quat) -> Self {
+        Self {
+            expression: Expression::Idle,
+            facing,
+            pose: Expression::Idle.pose(),
+            happy_seconds: 0.0,
+            next_blink_seconds: next_blink_seconds(),
+            blink_seconds: 0.0,
+        }
+    }
+
+    fn target_pose(&self) -> FacePose {
+        let mut pose = self.expression.pose();
+        if self.blink_seconds > 0.0 {
+            pose.eyelids_closed = 1.0;
+        }
+
+        pose
+    }
+}
+
+/// The entities of the head model of a snake, a child of the snake.
+#[derive(Component)]
+pub struct SnakeHead {
+    pub entity: Entity,
+    eyelids: [Entity; 2],
+    pupils: [Entity; 2],
+    mouth: Entity,
+}
+
+fn next_blink_seconds() -> f32 {
+    thread_rng().gen_range(2.0..5.0)
+}
+
+/// The rotation looking toward `direction` with the top of the head up, or turned away from
+/// where the head looked before when moving straight up or down.
+fn head_rotation(direction: Vec3, up: Vec3, current: Quat) -> Quat {
+    if direction == Vec3::ZERO {
+        return current;
+    }
+
+    let mut head_up = up;
+
+    if direction.cross(head_up).length_squared() < 0.01 {
+        head_up = -(current * Vec3::NEG_Z) * direction.dot(up).signum();
+    }
+    if direction.cross(head_up).length_squared() < 0.01 {
+        head_up = current * Vec3::Y;
+    }
+
+    Transform::IDENTITY.looking_at(direction, head_up).rotation
+}
+
+/// A four sided pyramid standing on the origin, one unit wide and high.
+fn cat_ear_mesh() -> Mesh {
+    let tip = Vec3::Y;
+    let corners = [
+        Vec3::new(-0.5, 0.0, 0.5),
+        Vec3::new(0.5, 0.0, 0.5),
+        Vec3::new(0.5, 0.0, -0.5),
+        Vec3::new(-0.5, 0.0, -0.5),
+    ];
+
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+
+    for (index, a) in corners.iter().enumerate() {
+        let b = corners[(index + 1) % corners.len()];
+        let normal = (b - *a).cross(tip - *a).normalize();
+
+        for corner in [*a, b, tip] {
+            positions.push(corner.to_array());
+            normals.push(normal.to_array());
+        }
+    }
+
+    let indices = (0..positions.len() as u32).collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+impl<'a> MaterialMeshBuilder<'a> {
+    pub fn build_head_piece(
+        &mut self,
+        mesh_shape: MeshShape,
+        color: ThemeColor,
+        transform: Transform,
+    ) -> PbrBundle {
+        let mesh = match mesh_shape {
+            MeshShape::CatEar => self.mesh(mesh_shape, cat_ear_mesh),
+            _ => self.mesh(mesh_shape, || {
+                Mesh::from(shape::UVSphere {
+                    radius: 1.0,
+                    sectors: 24,
+                    stacks: 16,
+                })
+            }),
+        };
+
+        PbrBundle {
+            mesh,
+            material: self.material(color),
+            transform,
+            ..default()
+        }
+    }
+}
+
+fn spawn_snake_head_system(
+    mut commands: Commands,
+    snakes: Query<(Entity, &Snake), Without<SnakeHead>>,
+    level: Option<Res<LevelInstance>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
+) {
+    let up = -level
+        .map_or(IVec3::NEG_Y, |level| level.gravity())
+        .as_vec3();
+
+    for (snake_entity, snake) in &snakes {
+        let mut builder = MaterialMeshBuilder {
+            meshes: meshes.as_mut(),
+            materials: materials.as_mut(),
+            cache: cache.as_mut(),
+        };
+
+        let fur = ThemeColor::Snake {
+            index: snake.index() as usize,
+            part: 0,
+        };
+        let mirror = [Vec3::new(-1.0, 1.0, 1.0), Vec3::ONE];
+
+        let skull = builder.build_head_piece(
+            MeshShape::Sphere,
+            fur,
+            Transform::from_scale(Vec3::new(0.46, 0.4, 0.42)),
+        );
+        let ears = mirror.map(|side| {
+            builder.build_head_piece(
+                MeshShape::CatEar,
+                fur,
+                Transform::from_translation(Vec3::new(0.22, 0.25, 0.0) * side)
+                    .with_rotation(Quat::from_rotation_z(-0.35 * side.x))
+                    .with_scale(Vec3::new(0.22, 0.3, 0.14)),
+            )
+        });
+        let eyes = mirror.map(|side| {
+            builder.build_head_piece(
+                MeshShape::Sphere,
+                ThemeColor::Eye,
+                Transform::from_translation(EYE_POSITION * side).with_scale(EYE_SCALE),
+            )
+        });
+        let pupils = mirror.map(|side| {
+            builder.build_head_piece(
+                MeshShape::Sphere,
+                ThemeColor::Pupil,
+                Transform::from_translation(EYE_POSITION * side + PUPIL_OFFSET),
+            )
+        });
+        let eyelids = mirror.map(|side| {
+            builder.build_head_piece(
+                MeshShape::Sphere,
+                fur,
+                Transform::from_translation(EYE_POSITION * side),
+            )
+        });
+        let mouth = builder.build_head_piece(
+            MeshShape::Sphere,
+            ThemeColor::Mouth,
+            Transform::from_translation(MOUTH_POSITION),
+        );
+
+        let mut spawn_pieces = |pieces: Vec<PbrBundle>| {
+            pieces
+                .into_iter()
+                .map(|piece| commands.spawn(piece).id())
+                .collect::<Vec<_>>()
+        };
+        let eyelids = spawn_pieces(eyelids.into());
+        let pupils = spawn_pieces(pupils.into());
+        let mouth = spawn_pieces(vec![mouth])[0];
+        let mut pieces = spawn_pieces(std::iter::once(skull).chain(ears).chain(eyes).collect());
+        pieces.extend(eyelids.iter().chain(&pupils).chain([&mouth]));
+
+        let entity = commands
+            .spawn((SpatialBundle::default(), Name::new("Snake Head")))
+            .push_children(&pieces)
+            .id();
+
+        let facing = head_rotation(snake.head_direction().as_vec3(), up, Quat::IDENTITY);
+        commands.entity(snake_entity).add_child(entity).insert((
+            SnakeHead {
+                entity,
+                eyelids: [eyelids[0], eyelids[1]],
+                pupils: [pupils[0], pupils[1]],
+                mouth,
+            },
+            HeadAnimation::new(facing),
+        ));
+    }
+}
+
+/// Pick the expression of the snakes and count down their blinks.
+fn update_head_expression_system(
+    time: Res<Time>,
+    level: Res<LevelInstance>,
+    mut snake_reach_goal_event: EventReader<SnakeReachGoalEvent>,
+    mut snakes: Query<(&Snake, &mut HeadAnimation, Option<&GravityFall>)>,
+) {
+    for event in snake_reach_goal_event.iter() {
+        if let Ok((_, mut animation, _)) = snakes.get_mut(event.0) {
+            animation.happy_seconds = HAPPY_SECONDS;
+        }
+    }
+
+    let delta = time.delta_seconds();
+
+    for (snake, mut animation, gravity_fall) in &mut snakes {
+        animation.happy_seconds = (animation.happy_seconds - delta).max(0.0);
+
+        let neck = snake.head_position() - snake.head_direction();
+        let food_next_to_head = NEIGHBOURS
+            .iter()
+            .map(|direction| snake.head_position() + *direction)
+            .any(|position| position != neck && level.is_food(position));
+
+        animation.expression = if animation.happy_seconds > 0.0 {
+            Expression::Happy
+        } else if gravity_fall.is_some() {
+            Expression::Surprised
+        } else if food_next_to_head {
+            Expression::Eating
+        } else {
+            Expression::Idle
+        };
+
+        // Only idle snakes blink, the other expressions keep their eyes open.
+        if animation.blink_seconds > 0.0 {
+            animation.blink_seconds -= delta;
+        } else if animation.expression == Expression::Idle {
+            animation.next_blink_seconds -= delta;
+            if animation.next_blink_seconds <= 0.0 {
+                animation.blink_seconds = BLINK_SECONDS;
+                animation.next_blink_seconds = next_blink_seconds();
+            }
+        }
+    }
+}
+
+/// Place the heads on the first part of the snakes, turn them and pose their faces.
+#[allow(clippy::type_complexity)]
+fn animate_snake_head_system(
+    time: Res<Time>,
+    level: Option<Res<LevelInstance>>,
+    mut snakes: Query<(
+        &Snake,
+        &Transform,
+        &SnakeHead,
+        &mut HeadAnimation,
+        &Children,
+    )>,
+    parts: Query<(&SnakePart, &Transform, Option<&PartClipper>)>,
+    mut pieces: Query<(&mut Transform, &mut Visibility), (Without<Snake>, Without<SnakePart>)>,
+) {
+    let up = -level
+        .map_or(IVec3::NEG_Y, |level| level.gravity())
+        .as_vec3();
+    let delta = time.delta_seconds();
+
+    for (snake, snake_transform, head, mut animation, children) in &mut snakes {
+        let head_part = children
+            .iter()
+            .filter_map(|child| parts.get(*child).ok())
+            .find(|(part, ..)| part.part_index == 0);
+
+        let Ok((mut head_transform, mut head_visibility)) = pieces.get_mut(head.entity) else {
+            continue;
+        };
+
+        head_visibility.is_visible = head_part.is_some();
+        let Some((_, part_transform, clipper)) = head_part else {
+            continue;
+        };
+
+        let direction = snake.head_direction().as_vec3();
+        let mut position = part_transform.translation;
+        let mut scale = 1.0;
+
+        // The head shrinks into the exit like the body does.
+        if let Some(clipper) = clipper {
+            let clip_position = clipper.clip_position.as_vec3() - snake_transform.translation;
+            let distance = (clip_position - position).dot(direction);
+            position += direction * distance.min(0.0);
+            scale = distance.clamp(0.0, 1.0);
+        }
+
+        let target = head_rotation(direction, up, animation.facing);
+        let turn = 1.0 - (-TURN_SPEED * delta).exp();
+        animation.facing = animation.facing.slerp(target, turn);
+
+        let target_pose = animation.target_pose();
+        let ease = if animation.blink_seconds > 0.0 {
+            1.0
+        } else {
+            1.0 - (-POSE_SPEED * delta).exp()
+        };
+        animation.pose = animation.pose.lerp(target_pose, ease);
+        let pose = animation.pose;
+
+        *head_transform = Transform::from_translation(position)
+            .with_rotation(animation.facing)
+            .with_scale(Vec3::splat(scale));
+
+        for eyelid in head.eyelids {
+            if let Ok((mut transform, mut visibility)) = pieces.get_mut(eyelid) {
+                // Scaled to nothing, the normals of the lid would be broken, so it's hidden.
+                visibility.is_visible = pose.eyelids_closed > 0.05;
+                transform.scale = EYELID_SCALE * Vec3::new(1.0, pose.eyelids_closed, 1.0);
+            }
+        }
+
+        for pupil in head.pupils {
+            if let Ok((mut transform, _)) = pieces.get_mut(pupil) {
+                transform.scale = Vec3::new(0.06 * pose.pupil_width, 0.07, 0.03);
+            }
+        }
+
+        if let Ok((mut transform, _)) = pieces.get_mut(head.mouth) {
+            transform.scale =
+                Vec3::new(0.1 * pose.mouth_width, 0.02 + 0.12 * pose.mouth_open, 0.05);
+        }
+    }
+}
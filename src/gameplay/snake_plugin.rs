@@ -1,13 +1,17 @@
-use bevy::{prelude::*, render::primitives::Aabb, transform::TransformSystem};
+use bevy::{prelude::*, render::primitives::Aabb, transform::TransformSystem, utils::HashMap};
 use iyes_loopless::prelude::{ConditionHelpers, IntoConditionalSystem};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
 use crate::{
     gameplay::commands::SnakeCommands,
-    gameplay::game_constants_plugin::SNAKE_COLORS,
     gameplay::movement_plugin::{GravityFall, MoveCommand, PushedAnim},
+    gameplay::npc_plugin::NpcSnake,
+    gameplay::theme_plugin::{snake_shades, snake_stripe, Theme, ThemeColor},
     gameplay::undo::{SnakeHistory, UndoEvent},
-    level::level_instance::{LevelGridEntity, LevelInstance},
+    level::{
+        level_instance::{LevelGridEntity, LevelInstance},
+        model_catalog::ModelPivot,
+    },
     utils::{ray_from_screen_space, ray_intersects_aabb},
     GameState,
 };
@@ -18,7 +22,8 @@ pub struct SnakePlugin;
 
 impl Plugin for SnakePlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<DespawnSnakePartEvent>()
+        app.init_resource::<MeshMaterialCache>()
+            .add_event::<DespawnSnakePartEvent>()
             .add_event::<DespawnSnakeEvent>()
             .add_event::<DespawnSnakePartsEvent>()
             .add_system(select_snake_mouse_system.run_in_state(GameState::Game))
@@ -26,6 +31,7 @@ impl Plugin for SnakePlugin {
                 CoreStage::PostUpdate,
                 update_snake_transforms_system
                     .run_in_state(GameState::Game)
+                    .label(SnakeStages::UpdateTransforms)
                     .before(TransformSystem::TransformPropagate),
             )
             .add_system_to_stage(
@@ -51,6 +57,11 @@ impl Plugin for SnakePlugin {
     }
 }
 
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+pub enum SnakeStages {
+    UpdateTransforms,
+}
+
 pub type SnakeElement = (IVec3, IVec3);
 pub type SnakeTemplate = Vec<SnakeElement>;
 
@@ -63,8 +74,16 @@ pub struct DespawnSnakeEvent(pub i32);
 #[derive(PartialEq, Eq)]
 pub struct DespawnSnakePartsEvent(pub i32);
 
+/// One of the players controlling snakes, there are two only in co-op.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Player {
+    One,
+    Two,
+}
+
+/// The snake controlled by a player.
 #[derive(Component)]
-pub struct SelectedSnake;
+pub struct SelectedSnake(pub Player);
 
 #[derive(Component)]
 pub struct Active;
@@ -81,26 +100,141 @@ pub struct SnakePartBundle {
     pub shape: PbrBundle,
 }
 
+/// The meshes built by [`MaterialMeshBuilder`], each shape is created once.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MeshShape {
+    SnakeHead,
+    SnakePart,
+    Box,
+    BreakableWall,
+    Food,
+    Trigger,
+    GravitySwitch,
+    Spike,
+    /// A sphere of radius one, scaled into the pieces of the snake heads.
+    Sphere,
+    CatEar,
+}
+
+/// The handles shared by every entity built with [`MaterialMeshBuilder`], so spawning the same
+/// entities again doesn't add assets. The materials take their color from the active theme.
+#[derive(Resource, Default)]
+pub struct MeshMaterialCache {
+    meshes: HashMap<MeshShape, Handle<Mesh>>,
+    materials: HashMap<ThemeColor, Handle<StandardMaterial>>,
+    theme: Theme,
+    /// The colors the level gives to some of its snakes, by snake index.
+    snake_colors: BTreeMap<usize, Color>,
+    color_blind: bool,
+    snake_body_material: Option<Handle<StandardMaterial>>,
+}
+
+impl MeshMaterialCache {
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn color(&self, color: ThemeColor) -> Color {
+        match color {
+            ThemeColor::Snake { index, part } => match self.snake_colors.get(&index) {
+                Some(snake_color) => snake_shades(*snake_color)[part % 2],
+                None => self.theme.snake_colors(index, self.color_blind)[part % 2],
+            },
+            _ => self.theme.color(color),
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme, materials: &mut Assets<StandardMaterial>) {
+        self.theme = theme;
+        self.recolor(materials);
+    }
+
+    pub fn set_snake_colors(
+        &mut self,
+        snake_colors: &BTreeMap<usize, Color>,
+        materials: &mut Assets<StandardMaterial>,
+    ) {
+        if self.snake_colors != *snake_colors {
+            self.snake_colors = snake_colors.clone();
+            self.recolor(materials);
+        }
+    }
+
+    pub fn set_color_blind(&mut self, color_blind: bool, materials: &mut Assets<StandardMaterial>) {
+        self.color_blind = color_blind;
+        self.recolor(materials);
+    }
+
+    /// Update the materials already built, entities keep their handles.
+    fn recolor(&self, materials: &mut Assets<StandardMaterial>) {
+        for (color, handle) in &self.materials {
+            if let Some(material) = materials.get_mut(handle) {
+                material.base_color = self.color(*color);
+            }
+        }
+    }
+}
+
 pub struct MaterialMeshBuilder<'a> {
     pub meshes: &'a mut Assets<Mesh>,
     pub materials: &'a mut Assets<StandardMaterial>,
+    pub cache: &'a mut MeshMaterialCache,
 }
 
 impl<'a> MaterialMeshBuilder<'a> {
+    /// The mesh of the shape, built the first time it is needed.
+    pub fn mesh(&mut self, shape: MeshShape, build: impl FnOnce() -> Mesh) -> Handle<Mesh> {
+        let meshes = &mut self.meshes;
+        self.cache
+            .meshes
+            .entry(shape)
+            .or_insert_with(|| meshes.add(build()))
+            .clone()
+    }
+
+    /// A plain material of the theme color, shared by everything of that color.
+    pub fn material(&mut self, color: ThemeColor) -> Handle<StandardMaterial> {
+        if let Some(handle) = self.cache.materials.get(&color) {
+            return handle.clone();
+        }
+
+        let handle = self.materials.add(self.cache.color(color).into());
+        self.cache.materials.insert(color, handle.clone());
+        handle
+    }
+
+    /// The material of the snake bodies, white so the colors of their vertices show.
+    pub fn snake_body_material(&mut self) -> Handle<StandardMaterial> {
+        let materials = &mut self.materials;
+        self.cache
+            .snake_body_material
+            .get_or_insert_with(|| materials.add(Color::WHITE.into()))
+            .clone()
+    }
+
     pub fn build_part(
         &mut self,
         position: IVec3,
         snake_index: i32,
         part_index: usize,
     ) -> SnakePartBundle {
-        let color = SNAKE_COLORS[snake_index as usize][part_index % 2];
-        let size = if part_index == 0 { 0.8 } else { 0.7 };
+        let color = ThemeColor::Snake {
+            index: snake_index as usize,
+            part: snake_stripe(snake_index as usize, part_index),
+        };
+        let (shape, size) = if part_index == 0 {
+            (MeshShape::SnakeHead, 0.8)
+        } else {
+            (MeshShape::SnakePart, 0.7)
+        };
 
         SnakePartBundle {
             shape: PbrBundle {
-                mesh: self.meshes.add(Mesh::from(shape::Cube { size })),
-                material: self.materials.add(color.into()),
+                mesh: self.mesh(shape, || Mesh::from(shape::Cube { size })),
+                material: self.material(color),
                 global_transform: GlobalTransform::from_translation(position.as_vec3()),
+                // The body mesh of the snake draws the parts, they are only shown in the editor.
+                visibility: Visibility::INVISIBLE,
                 ..default()
             },
             part: SnakePart {
@@ -168,9 +302,8 @@ impl Snake {
         self.parts.back().unwrap().0
     }
 
-    pub fn is_standing(&self) -> bool {
-        (self.parts.front().unwrap().0.y - self.parts.back().unwrap().0.y)
-            == (self.len() - 1) as i32
+    pub fn is_standing(&self, gravity: IVec3) -> bool {
+        (self.head_position() - self.tail_position()).dot(-gravity) == (self.len() - 1) as i32
     }
 
     pub fn occupies_position(&self, position: IVec3) -> bool {
@@ -259,9 +392,14 @@ pub fn update_snake_transforms_system(
         (With<Active>, Without<SnakePart>),
     >,
     mut part_query: Query<(&mut Transform, &SnakePart), With<SnakePart>>,
+    level: Option<Res<LevelInstance>>,
 ) {
+    let up = -level
+        .map_or(IVec3::NEG_Y, |level| level.gravity())
+        .as_vec3();
+
     for (snake, mut transform, _, _, pushed_anim, fall) in &mut snake_query {
-        let fall_offset = fall.map_or(Vec3::ZERO, |gravity_fall| gravity_fall.relative_z * Vec3::Y);
+        let fall_offset = fall.map_or(Vec3::ZERO, |gravity_fall| gravity_fall.relative_z * up);
 
         let push_offset = pushed_anim.map_or(Vec3::ZERO, |command| {
             let initial_offset = -command.direction;
@@ -273,7 +411,9 @@ pub fn update_snake_transforms_system(
 
     for (snake, _, children, move_command, _, _) in &mut snake_query {
         for child in children {
-            let (mut part_transform, part) = part_query.get_mut(*child).unwrap();
+            let Ok((mut part_transform, part)) = part_query.get_mut(*child) else {
+                continue;
+            };
             if part.part_index > snake.parts().len() - 1 {
                 continue;
             }
@@ -297,6 +437,7 @@ pub fn update_movable_transforms_system(
         (
             &GridEntity,
             &mut Transform,
+            Option<&ModelPivot>,
             Option<&PushedAnim>,
             Option<&GravityFall>,
         ),
@@ -305,16 +446,25 @@ pub fn update_movable_transforms_system(
             Or<(With<PushedAnim>, With<GravityFall>)>,
         )>,
     >,
+    level: Option<Res<LevelInstance>>,
 ) {
-    for (grid_entity, mut transform, pushed_anim, fall) in &mut moving_entitites {
-        let fall_offset = fall.map_or(Vec3::ZERO, |gravity_fall| gravity_fall.relative_z * Vec3::Y);
+    let up = -level
+        .map_or(IVec3::NEG_Y, |level| level.gravity())
+        .as_vec3();
+
+    for (grid_entity, mut transform, pivot, pushed_anim, fall) in &mut moving_entitites {
+        let fall_offset = fall.map_or(Vec3::ZERO, |gravity_fall| gravity_fall.relative_z * up);
 
         let push_offset = pushed_anim.map_or(Vec3::ZERO, |command| {
             let initial_offset = -command.direction;
             initial_offset.lerp(Vec3::ZERO, command.lerp_time)
         });
 
-        transform.translation = grid_entity.position.as_vec3() + push_offset + fall_offset;
+        // Models of the catalog are drawn away from the grid position.
+        let pivot_offset = pivot.map_or(Vec3::ZERO, |pivot| transform.rotation * pivot.0);
+
+        transform.translation =
+            grid_entity.position.as_vec3() + pivot_offset + push_offset + fall_offset;
     }
 }
 
@@ -339,8 +489,8 @@ pub fn select_snake_mouse_system(
     windows: Res<Windows>,
     mut commands: Commands,
     camera: Query<(&Camera, &GlobalTransform)>,
-    selected_snake: Query<Entity, With<SelectedSnake>>,
-    unselected_snakes: Query<(Entity, &Snake), Without<SelectedSnake>>,
+    selected_snakes: Query<(Entity, &SelectedSnake)>,
+    unselected_snakes: Query<(Entity, &Snake), (Without<SelectedSnake>, Without<NpcSnake>)>,
 ) {
     if !buttons.just_pressed(MouseButton::Left) {
         return;
@@ -355,8 +505,6 @@ pub fn select_snake_mouse_system(
     let (camera, camera_transform) = camera.single();
     let ray = ray_from_screen_space(mouse_position, camera, camera_transform);
 
-    let selected_snake_entity = selected_snake.single();
-
     let test_aabb = Aabb::from_min_max(0.5 * Vec3::NEG_ONE, 0.5 * Vec3::ONE);
 
     for (entity, snake) in unselected_snakes.iter() {
@@ -369,11 +517,16 @@ pub fn select_snake_mouse_system(
             continue;
         }
 
-        commands
-            .entity(selected_snake_entity)
-            .remove::<SelectedSnake>();
+        // The mouse always selects for the first player.
+        for (selected_snake_entity, selected_snake) in &selected_snakes {
+            if selected_snake.0 == Player::One {
+                commands
+                    .entity(selected_snake_entity)
+                    .remove::<SelectedSnake>();
+            }
+        }
 
-        commands.entity(entity).insert(SelectedSnake);
+        commands.entity(entity).insert(SelectedSnake(Player::One));
     }
 }
 
@@ -385,7 +538,7 @@ pub fn respawn_snake_on_fall_system(
     mut snake_query: Query<(Entity, &Snake), With<GravityFall>>,
 ) {
     for (snake_entity, snake) in snake_query.iter_mut() {
-        if snake.head_position().y >= -2 {
+        if !level.is_below_level(snake.head_position(), 2) {
             return;
         }
 
@@ -397,7 +550,9 @@ pub fn respawn_snake_on_fall_system(
 
         commands.entity(snake_entity).remove::<GravityFall>();
 
-        trigger_undo_event.send(UndoEvent);
+        // Rewind the move of the player that made the snake fall.
+        let player = snake_history.last_player().unwrap_or(Player::One);
+        trigger_undo_event.send(UndoEvent(player));
     }
 }
 
@@ -465,3 +620,172 @@ fn despawn_snake_parts_system(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetPlugin, ecs::system::SystemState, gltf::Gltf};
+
+    use super::*;
+    use crate::{
+        gameplay::level_entities::*,
+        level::model_catalog::ModelCatalog,
+        library::{AssetLibrary, GameAssets},
+    };
+
+    #[test]
+    fn respawning_entities_reuses_the_cached_assets() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .init_resource::<MeshMaterialCache>();
+
+        let mut state: SystemState<(
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+            ResMut<MeshMaterialCache>,
+        )> = SystemState::new(&mut app.world);
+        let (mut meshes, mut materials, mut cache) = state.get_mut(&mut app.world);
+
+        let mut builder = MaterialMeshBuilder {
+            meshes: meshes.as_mut(),
+            materials: materials.as_mut(),
+            cache: cache.as_mut(),
+        };
+
+        let spawn_all = |builder: &mut MaterialMeshBuilder| {
+            for part_index in 0..4 {
+                builder.build_part(IVec3::X * part_index as i32, 0, part_index);
+            }
+            builder.build_food_mesh(IVec3::ZERO);
+            builder.build_box_mesh(IVec3::ZERO);
+            builder.build_trigger_mesh(IVec3::ZERO);
+            builder.build_spike_mesh(IVec3::ZERO);
+        };
+
+        spawn_all(&mut builder);
+        let counts = (builder.meshes.len(), builder.materials.len());
+
+        for _ in 0..10 {
+            spawn_all(&mut builder);
+        }
+
+        assert_eq!((builder.meshes.len(), builder.materials.len()), counts);
+    }
+
+    #[test]
+    fn level_entities_share_their_assets_when_spawned_again() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_asset::<Gltf>()
+            .init_resource::<MeshMaterialCache>();
+
+        let cube_mesh = app
+            .world
+            .resource_mut::<Assets<Mesh>>()
+            .add(shape::Cube::default().into());
+        let default_cube_material = app
+            .world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::WHITE.into());
+        let assets = GameAssets {
+            move_effect: default(),
+            outline_texture: default(),
+            cube_mesh,
+            default_cube_material,
+            default_material: default(),
+            goal_light_cone_mesh: default(),
+            goal_light_cone_material: default(),
+            goal_active_mesh: default(),
+            goal_inactive_mesh: default(),
+            kitchen_model: default(),
+        };
+        let library = AssetLibrary {
+            models: default(),
+            packs: default(),
+            catalog: default(),
+        };
+        let templates = RespawnableEntities::default();
+        let catalog = ModelCatalog::default();
+
+        #[allow(clippy::type_complexity)]
+        let mut state: SystemState<(
+            Commands,
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+            ResMut<MeshMaterialCache>,
+            Res<Assets<Gltf>>,
+        )> = SystemState::new(&mut app.world);
+
+        let mut spawn_all = |world: &mut World| {
+            let entities = {
+                let (mut commands, mut meshes, mut materials, mut cache, assets_gltf) =
+                    state.get_mut(world);
+                let mut builder = MaterialMeshBuilder {
+                    meshes: meshes.as_mut(),
+                    materials: materials.as_mut(),
+                    cache: cache.as_mut(),
+                };
+                let respawner = EntityRespawner {
+                    templates: &templates,
+                    library: &library,
+                    assets_gltf: &assets_gltf,
+                    catalog: &catalog,
+                };
+
+                let position = IVec3::ZERO;
+                [
+                    spawn_wall(&mut commands, &position, &assets),
+                    spawn_breakable_wall(&mut builder, &mut commands, &position, default()),
+                    spawn_gravity_switch(&mut builder, &mut commands, &position),
+                    spawn_food(&mut builder, &mut commands, &position),
+                    // What undo spawns for broken walls and eaten food.
+                    respawner.respawn_breakable_wall(
+                        &mut builder,
+                        &mut commands,
+                        position,
+                        default(),
+                    ),
+                    respawner.respawn_food(&mut builder, &mut commands, position),
+                ]
+            };
+            state.apply(world);
+            entities
+        };
+
+        let asset_counts = |world: &World| {
+            (
+                world.resource::<Assets<Mesh>>().len(),
+                world.resource::<Assets<StandardMaterial>>().len(),
+            )
+        };
+        let handles = |world: &World, entity: Entity| {
+            (
+                world.get::<Handle<Mesh>>(entity).unwrap().clone(),
+                world
+                    .get::<Handle<StandardMaterial>>(entity)
+                    .unwrap()
+                    .clone(),
+            )
+        };
+
+        let first = spawn_all(&mut app.world);
+        let counts = asset_counts(&app.world);
+
+        assert_eq!(handles(&app.world, first[4]), handles(&app.world, first[1]));
+        assert_eq!(handles(&app.world, first[5]), handles(&app.world, first[3]));
+
+        for _ in 0..10 {
+            let entities = spawn_all(&mut app.world);
+            for (entity, first) in entities.into_iter().zip(first) {
+                assert_eq!(handles(&app.world, entity), handles(&app.world, first));
+            }
+        }
+
+        assert_eq!(asset_counts(&app.world), counts);
+    }
+}
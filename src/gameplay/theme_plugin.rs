@@ -0,0 +1,291 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::Args,
+    level::{
+        level_pack::{ActivePack, LevelPack},
+        level_template::{LevelTemplate, LoadedLevel},
+    },
+    library::{AssetLibrary, GameAssets},
+};
+
+use super::{
+    game_constants_plugin::{
+        BACKGROUND_COLOR, BREAKABLE_WALL_COLOR, COLOR_BLIND_SNAKE_COLORS, FOOD_COLOR,
+        GRAVITY_SWITCH_COLOR, SNAKE_COLORS, SPIKE_COLOR,
+    },
+    snake_plugin::MeshMaterialCache,
+};
+
+pub const THEME_EXTENSION: &str = "theme";
+
+/// Which of its two colors each part of a snake uses, so snakes can be told apart by their
+/// stripes without relying on hue. Every pattern starts with the head.
+const SNAKE_PATTERNS: [&[usize]; 4] = [&[0, 1], &[0, 0, 1], &[0, 1, 1], &[0, 0, 0, 1, 1]];
+
+/// The hue step between generated snake colors, spreads any number of snakes around the wheel.
+const GOLDEN_ANGLE: f32 = 137.507_76;
+
+/// The theme used by levels and packs that don't pick one.
+pub const DEFAULT_THEME: &str = "themes/default.theme";
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Theme>()
+            .add_asset_loader(ThemeLoader)
+            .add_startup_system(load_default_theme_system)
+            .add_startup_system(color_blind_mode_system)
+            .add_system(select_level_theme_system)
+            .add_system(apply_theme_system.after(select_level_theme_system));
+    }
+}
+
+/// A color of the theme, the materials built with it follow the changes of the theme.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ThemeColor {
+    Spike,
+    BreakableWall,
+    Food,
+    GravitySwitch,
+    Box,
+    Trigger,
+    Eye,
+    Pupil,
+    Mouth,
+    /// `part` is the index of the color in the palette entry of the snake, see [`snake_stripe`].
+    Snake {
+        index: usize,
+        part: usize,
+    },
+}
+
+/// The colors and wall look of the levels, read from the `.theme` files of the themes folder.
+#[derive(Deserialize, Serialize, TypeUuid, Debug, Clone, PartialEq)]
+#[uuid = "b7e2c4d1-6a3f-4e85-9c10-3d5f8a2e7b64"]
+#[serde(default)]
+pub struct Theme {
+    /// The clear color, levels can override it with their lighting.
+    pub background: Color,
+    /// Multiplies the outline texture of the default walls.
+    pub wall_tint: Color,
+    /// Asset path of the texture of the default walls.
+    pub wall_texture: String,
+    pub spike: Color,
+    pub breakable_wall: Color,
+    pub food: Color,
+    pub gravity_switch: Color,
+    #[serde(rename = "box")]
+    pub box_color: Color,
+    pub trigger: Color,
+    /// The goal in the level previews, the game draws its model instead.
+    pub goal: Color,
+    /// The colors of the faces of the snakes.
+    pub eye: Color,
+    pub pupil: Color,
+    pub mouth: Color,
+    /// The two colors of each snake, snakes past the end of the list get generated colors.
+    pub snakes: Vec<[Color; 2]>,
+    /// Replaces `snakes` in color-blind mode, colors that differ in lightness as well as hue.
+    pub color_blind_snakes: Vec<[Color; 2]>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: BACKGROUND_COLOR,
+            wall_tint: Color::rgb(0.8, 0.7, 0.6),
+            wall_texture: "outline.png".to_owned(),
+            spike: SPIKE_COLOR,
+            breakable_wall: BREAKABLE_WALL_COLOR,
+            food: FOOD_COLOR,
+            gravity_switch: GRAVITY_SWITCH_COLOR,
+            box_color: Color::BEIGE,
+            trigger: Color::GRAY,
+            goal: Color::rgb(1.0, 0.9, 0.3),
+            eye: Color::rgb(0.95, 0.95, 0.85),
+            pupil: Color::rgb(0.1, 0.1, 0.1),
+            mouth: Color::rgb(0.45, 0.15, 0.2),
+            snakes: SNAKE_COLORS.to_vec(),
+            color_blind_snakes: COLOR_BLIND_SNAKE_COLORS.to_vec(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn color(&self, color: ThemeColor) -> Color {
+        match color {
+            ThemeColor::Spike => self.spike,
+            ThemeColor::BreakableWall => self.breakable_wall,
+            ThemeColor::Food => self.food,
+            ThemeColor::GravitySwitch => self.gravity_switch,
+            ThemeColor::Box => self.box_color,
+            ThemeColor::Trigger => self.trigger,
+            ThemeColor::Eye => self.eye,
+            ThemeColor::Pupil => self.pupil,
+            ThemeColor::Mouth => self.mouth,
+            ThemeColor::Snake { index, part } => self.snake_colors(index, false)[part % 2],
+        }
+    }
+
+    /// The colors of the snake at `index`, from the palette or generated past its end.
+    pub fn snake_colors(&self, index: usize, color_blind: bool) -> [Color; 2] {
+        let palette = if color_blind {
+            &self.color_blind_snakes
+        } else {
+            &self.snakes
+        };
+
+        palette.get(index).copied().unwrap_or_else(|| {
+            let hue = (index as f32 * GOLDEN_ANGLE) % 360.0;
+            snake_shades(Color::hsl(hue, 0.6, 0.45))
+        })
+    }
+}
+
+/// The two colors of a snake drawn with a single color, the second one is lighter.
+pub fn snake_shades(color: Color) -> [Color; 2] {
+    let lighter = match color.as_hsla() {
+        Color::Hsla {
+            hue,
+            saturation,
+            lightness,
+            alpha,
+        } => Color::hsla(hue, saturation, (lightness + 0.15).min(0.9), alpha),
+        _ => color,
+    };
+
+    [color, lighter]
+}
+
+/// The color of the palette entry used by a part of the snake, following the stripes of the
+/// snake.
+pub fn snake_stripe(snake_index: usize, part_index: usize) -> usize {
+    let pattern = SNAKE_PATTERNS[snake_index % SNAKE_PATTERNS.len()];
+    pattern[part_index % pattern.len()]
+}
+
+/// The theme of the level being played, the default one until a level picks another.
+#[derive(Resource)]
+pub struct ActiveTheme {
+    pub path: String,
+    pub handle: Handle<Theme>,
+}
+
+fn load_default_theme_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ActiveTheme {
+        path: DEFAULT_THEME.to_owned(),
+        handle: asset_server.load(DEFAULT_THEME),
+    });
+}
+
+fn color_blind_mode_system(
+    args: Res<Args>,
+    mut cache: ResMut<MeshMaterialCache>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    cache.set_color_blind(args.color_blind, &mut materials);
+}
+
+/// Levels use their own theme, then the one of the active pack.
+fn select_level_theme_system(
+    asset_server: Res<AssetServer>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    level_templates: Res<Assets<LevelTemplate>>,
+    active_pack: Res<ActivePack>,
+    library: Option<Res<AssetLibrary>>,
+    packs: Res<Assets<LevelPack>>,
+    mut active_theme: ResMut<ActiveTheme>,
+) {
+    let Some(loaded_level) = loaded_level.filter(|level| level.is_changed()) else {
+        return;
+    };
+    let Some(template) = level_templates.get(&loaded_level.0) else {
+        return;
+    };
+
+    let pack_theme = library
+        .and_then(|library| active_pack.get(&library, &packs))
+        .and_then(|pack| pack.theme.clone());
+    let path = template
+        .theme
+        .clone()
+        .or(pack_theme)
+        .unwrap_or_else(|| DEFAULT_THEME.to_owned());
+
+    if path != active_theme.path {
+        *active_theme = ActiveTheme {
+            handle: asset_server.load(&path),
+            path,
+        };
+    }
+}
+
+/// Recolor the shared materials when the active theme changes or its file is edited.
+#[allow(clippy::too_many_arguments)]
+fn apply_theme_system(
+    mut commands: Commands,
+    mut theme_events: EventReader<AssetEvent<Theme>>,
+    active_theme: Res<ActiveTheme>,
+    themes: Res<Assets<Theme>>,
+    asset_server: Res<AssetServer>,
+    assets: Option<Res<GameAssets>>,
+    mut cache: ResMut<MeshMaterialCache>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    level_templates: Res<Assets<LevelTemplate>>,
+) {
+    let theme_edited = theme_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == active_theme.handle
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !theme_edited && !active_theme.is_changed() {
+        return;
+    }
+
+    let (Some(theme), Some(assets)) = (themes.get(&active_theme.handle), assets) else {
+        return;
+    };
+
+    cache.set_theme(theme.clone(), &mut materials);
+
+    if let Some(wall_material) = materials.get_mut(&assets.default_cube_material) {
+        wall_material.base_color = theme.wall_tint;
+        wall_material.base_color_texture = Some(asset_server.load(&theme.wall_texture));
+    }
+
+    let level_background = loaded_level
+        .and_then(|level| level_templates.get(&level.0))
+        .and_then(|template| template.lighting.background_color);
+    commands.insert_resource(ClearColor(level_background.unwrap_or(theme.background)));
+}
+
+#[derive(Default)]
+pub struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let theme = ron::de::from_bytes::<Theme>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(theme));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[THEME_EXTENSION]
+    }
+}
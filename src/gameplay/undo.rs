@@ -1,16 +1,21 @@
-use bevy::prelude::*;
+use std::ops::Range;
+
+use bevy::{gltf::Gltf, prelude::*, utils::HashSet};
 
 use crate::{
+    args::Args,
     gameplay::level_entities::*,
     gameplay::movement_plugin::GravityFall,
-    gameplay::snake_plugin::{set_snake_active, DespawnSnakePartEvent, Snake, SnakePart},
+    gameplay::snake_plugin::{set_snake_active, DespawnSnakePartEvent, Player, Snake, SnakePart},
     level::level_instance::{LevelGridEntity, LevelInstance},
+    level::model_catalog::ModelCatalog,
+    library::AssetLibrary,
 };
 
 use super::{
     level_entities::GridEntity,
     movement_plugin::MovableRegistry,
-    snake_plugin::{MaterialMeshBuilder, SnakeElement},
+    snake_plugin::{MaterialMeshBuilder, MeshMaterialCache, SnakeElement},
 };
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -19,6 +24,15 @@ pub enum LevelEntityUpdateEvent {
     FillPosition(IVec3),
 }
 
+impl LevelEntityUpdateEvent {
+    pub fn position(&self) -> IVec3 {
+        match self {
+            LevelEntityUpdateEvent::ClearPosition(position, _) => *position,
+            LevelEntityUpdateEvent::FillPosition(position) => *position,
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct BeginFall {
     // The initial position of the snake before falling.
@@ -36,8 +50,8 @@ pub struct EndFall {
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum MoveHistoryEvent {
-    /// A history event that marks a player move action.
-    PlayerSnakeMove,
+    /// A history event that marks a move action of a player.
+    PlayerSnakeMove(Player),
 
     /// History event for the snake moving one tile in a direction, storing the old tails for undo.
     SnakeMoveForward(SnakeElement),
@@ -56,6 +70,15 @@ pub enum MoveHistoryEvent {
 
     /// History event for a snake exiting the level through the goal.
     ExitLevel(Entity),
+
+    /// History event when the load on a breakable wall changes, storing the previous state.
+    LoadWall(IVec3, BreakableWallComponent),
+
+    /// History event when a breakable wall breaks and is despawned, storing its state.
+    BreakWall(IVec3, BreakableWallComponent),
+
+    /// History event when the gravity of the level is flipped, storing the previous gravity.
+    FlipGravity(IVec3),
 }
 
 #[derive(Clone)]
@@ -65,7 +88,8 @@ pub struct SnakeHistoryEvent {
     walkable_updates: Vec<LevelEntityUpdateEvent>,
 }
 
-pub struct UndoEvent;
+/// Undo the last move of a player, refused when a later move of the other player depends on it.
+pub struct UndoEvent(pub Player);
 
 /// A struct storing history events that can be undone.
 #[derive(Resource, Default)]
@@ -82,6 +106,61 @@ impl SnakeHistory {
         });
     }
 
+    /// The number of moves made by the players.
+    pub fn player_move_count(&self) -> u32 {
+        self.move_history
+            .iter()
+            .filter(|top| matches!(top.event, MoveHistoryEvent::PlayerSnakeMove(_)))
+            .count() as u32
+    }
+
+    /// The player who made the last move.
+    pub fn last_player(&self) -> Option<Player> {
+        self.move_history
+            .iter()
+            .rev()
+            .find_map(|top| match top.event {
+                MoveHistoryEvent::PlayerSnakeMove(player) => Some(player),
+                _ => None,
+            })
+    }
+
+    /// The history events of the last move of the player, from its marker to the next move.
+    fn last_move_range(&self, player: Player) -> Option<Range<usize>> {
+        let start = self
+            .move_history
+            .iter()
+            .rposition(|top| top.event == MoveHistoryEvent::PlayerSnakeMove(player))?;
+        let end = self.move_history[start + 1..]
+            .iter()
+            .position(|top| matches!(top.event, MoveHistoryEvent::PlayerSnakeMove(_)))
+            .map_or(self.move_history.len(), |offset| start + 1 + offset);
+
+        Some(start..end)
+    }
+
+    /// Players undo their own last move, unless a later move of the other player touched the
+    /// same entities or cells, or stands on the cells it changed.
+    pub fn can_undo(&self, player: Player, gravity: IVec3) -> bool {
+        let Some(range) = self.last_move_range(player) else {
+            return false;
+        };
+
+        let (entities, cells) = touched_by(&self.move_history[range.clone()]);
+        let (later_entities, later_cells) = touched_by(&self.move_history[range.end..]);
+
+        let flips_gravity = self.move_history[range.start..]
+            .iter()
+            .any(|top| matches!(top.event, MoveHistoryEvent::FlipGravity(_)));
+        let later_moves = range.end < self.move_history.len();
+
+        !(later_moves && flips_gravity)
+            && entities.is_disjoint(&later_entities)
+            && later_cells
+                .iter()
+                .all(|cell| !cells.contains(cell) && !cells.contains(&(*cell + gravity)))
+    }
+
     pub fn push_with_updates(
         &mut self,
         event: MoveHistoryEvent,
@@ -95,27 +174,35 @@ impl SnakeHistory {
         });
     }
 
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
     pub fn undo_last(
         &mut self,
+        player: Player,
         snakes: &mut Query<(Entity, &mut Snake)>,
         box_query: &mut Query<(Entity, &mut GridEntity), With<BoxComponent>>,
+        walls_query: &mut Query<(&GridEntity, &mut BreakableWallComponent), Without<BoxComponent>>,
         level: &mut LevelInstance,
         commands: &mut Commands,
         part_builder: &mut MaterialMeshBuilder,
+        respawner: &EntityRespawner,
         despawn_snake_part_event: &mut EventWriter<DespawnSnakePartEvent>,
     ) {
+        if !self.can_undo(player, level.gravity()) {
+            return;
+        }
+        let Some(range) = self.last_move_range(player) else {
+            return;
+        };
+
         let mut movable_registry = MovableRegistry::new(snakes, box_query);
 
-        // Undo the stack until we reach the last player action.
-        while let Some(top) = self.move_history.pop() {
-            if MoveHistoryEvent::PlayerSnakeMove == top.event {
-                return;
-            }
+        // Undo the events of the move from the last one, the later moves don't depend on them.
+        let last_move: Vec<SnakeHistoryEvent> = self.move_history.drain(range).collect();
+        for top in last_move.into_iter().skip(1).rev() {
+            let mut respawned_wall = None;
 
             match top.event {
-                MoveHistoryEvent::PlayerSnakeMove => {
-                    unreachable!("Should be handled as early return above.")
-                }
+                MoveHistoryEvent::PlayerSnakeMove(_) => {}
                 MoveHistoryEvent::SnakeMoveForward(old_tail) => {
                     let snake = movable_registry.get_mut_snake(&top.level_entity);
                     snake.move_back(&old_tail);
@@ -141,50 +228,119 @@ impl SnakeHistory {
                     snake.shrink();
                 }
                 MoveHistoryEvent::Eat(position) => {
-                    spawn_food(part_builder, commands, &position);
+                    respawner.respawn_food(part_builder, commands, position);
                 }
                 MoveHistoryEvent::ExitLevel(snake_entity) => {
                     let snake = movable_registry.get_mut_snake(&top.level_entity);
                     set_snake_active(part_builder, commands, snake, snake_entity);
                 }
+                MoveHistoryEvent::LoadWall(position, state) => {
+                    if let Some((_, mut wall)) = walls_query
+                        .iter_mut()
+                        .find(|(grid_entity, _)| grid_entity.position == position)
+                    {
+                        *wall = state;
+                    }
+                }
+                MoveHistoryEvent::FlipGravity(gravity) => {
+                    level.set_gravity(gravity);
+                }
+                MoveHistoryEvent::BreakWall(position, state) => {
+                    let entity =
+                        respawner.respawn_breakable_wall(part_builder, commands, position, state);
+                    respawned_wall = Some((
+                        position,
+                        LevelGridEntity::new(entity, EntityType::BreakableWall),
+                    ));
+                }
             }
 
             level.undo_updates(&top.walkable_updates);
+
+            // The level still references the despawned wall, point it to the new one.
+            if let Some((position, wall)) = respawned_wall {
+                level.mark_position_occupied(position, wall);
+            }
         }
     }
 }
 
+/// The entities and the cells changed by the history events.
+fn touched_by(events: &[SnakeHistoryEvent]) -> (HashSet<Entity>, HashSet<IVec3>) {
+    let mut entities = HashSet::new();
+    let mut cells = HashSet::new();
+
+    for top in events {
+        entities.insert(top.level_entity.entity);
+
+        cells.extend(
+            top.walkable_updates
+                .iter()
+                .map(LevelEntityUpdateEvent::position),
+        );
+
+        match &top.event {
+            MoveHistoryEvent::Eat(position)
+            | MoveHistoryEvent::LoadWall(position, _)
+            | MoveHistoryEvent::BreakWall(position, _) => {
+                cells.insert(*position);
+            }
+            MoveHistoryEvent::BeginFall(BeginFall { end: Some(end), .. }) => {
+                cells.extend(
+                    end.walkable_updates
+                        .iter()
+                        .map(LevelEntityUpdateEvent::position),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    (entities, cells)
+}
+
 pub fn keyboard_undo_system(
     keyboard: Res<Input<KeyCode>>,
+    args: Res<Args>,
     mut trigger_undo_event: EventWriter<UndoEvent>,
     falling_snakes: Query<(With<Snake>, With<GravityFall>)>,
 ) {
-    if !keyboard.just_pressed(KeyCode::Back) {
+    let player = if keyboard.just_pressed(KeyCode::Back) {
+        Player::One
+    } else if args.coop && keyboard.just_pressed(KeyCode::Delete) {
+        Player::Two
+    } else {
         return;
-    }
+    };
 
     if !falling_snakes.is_empty() {
         return;
     }
 
-    trigger_undo_event.send(UndoEvent);
+    trigger_undo_event.send(UndoEvent(player));
 }
 
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn undo_event_system(
     mut trigger_undo_event: EventReader<UndoEvent>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
     mut snake_history: ResMut<SnakeHistory>,
     mut level: ResMut<LevelInstance>,
+    respawnable_entities: Res<RespawnableEntities>,
+    library: Res<AssetLibrary>,
+    assets_gltf: Res<Assets<Gltf>>,
+    catalogs: Res<Assets<ModelCatalog>>,
     mut despawn_snake_part_event: EventWriter<DespawnSnakePartEvent>,
     mut commands: Commands,
     mut snake_query: Query<(Entity, &mut Snake)>,
     mut box_query: Query<(Entity, &mut GridEntity), With<BoxComponent>>,
+    mut walls_query: Query<(&GridEntity, &mut BreakableWallComponent), Without<BoxComponent>>,
 ) {
-    if trigger_undo_event.iter().next().is_none() {
+    let Some(UndoEvent(player)) = trigger_undo_event.iter().next() else {
         return;
-    }
+    };
 
     if snake_history.move_history.is_empty() {
         return;
@@ -193,14 +349,163 @@ pub fn undo_event_system(
     let mut part_builder = MaterialMeshBuilder {
         meshes: meshes.as_mut(),
         materials: materials.as_mut(),
+        cache: cache.as_mut(),
+    };
+
+    let respawner = EntityRespawner {
+        templates: &respawnable_entities,
+        library: &library,
+        assets_gltf: &assets_gltf,
+        catalog: ModelCatalog::of_library(&library, &catalogs),
     };
 
     snake_history.undo_last(
+        *player,
         &mut snake_query,
         &mut box_query,
+        &mut walls_query,
         &mut level,
         &mut commands,
         &mut part_builder,
+        &respawner,
         &mut despawn_snake_part_event,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetPlugin, ecs::system::SystemState};
+
+    use super::*;
+
+    #[test]
+    fn players_undo_their_last_move_unless_the_other_player_depends_on_it() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_asset::<Gltf>()
+            .add_event::<DespawnSnakePartEvent>()
+            .init_resource::<MeshMaterialCache>()
+            .init_resource::<LevelInstance>();
+
+        let templates = [
+            vec![(IVec3::new(1, 0, 0), IVec3::X), (IVec3::ZERO, IVec3::X)],
+            vec![
+                (IVec3::new(1, 0, 2), IVec3::X),
+                (IVec3::new(0, 0, 2), IVec3::X),
+            ],
+        ];
+        let snakes = templates.map(|template| app.world.spawn(Snake::new(&template, 0)).id());
+
+        let mut history = SnakeHistory::default();
+        let mut move_snake = |history: &mut SnakeHistory, player: Player, snake_entity| {
+            let level_entity = LevelGridEntity::new(snake_entity, EntityType::Snake);
+            let mut snake = app.world.get_mut::<Snake>(snake_entity).unwrap();
+            let old_tail = snake.tail();
+            snake.move_forward(IVec3::X);
+
+            history.push(MoveHistoryEvent::PlayerSnakeMove(player), level_entity);
+            history.push(MoveHistoryEvent::SnakeMoveForward(old_tail), level_entity);
+        };
+
+        move_snake(&mut history, Player::One, snakes[0]);
+        move_snake(&mut history, Player::Two, snakes[1]);
+
+        #[allow(clippy::type_complexity)]
+        let mut state: SystemState<(
+            Commands,
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+            ResMut<MeshMaterialCache>,
+            ResMut<LevelInstance>,
+            Res<Assets<Gltf>>,
+            EventWriter<DespawnSnakePartEvent>,
+            Query<(Entity, &mut Snake)>,
+            Query<(Entity, &mut GridEntity), With<BoxComponent>>,
+            Query<(&GridEntity, &mut BreakableWallComponent), Without<BoxComponent>>,
+        )> = SystemState::new(&mut app.world);
+
+        let library = AssetLibrary {
+            models: default(),
+            packs: default(),
+            catalog: default(),
+        };
+
+        let mut undo = |history: &mut SnakeHistory, player: Player| {
+            let (
+                mut commands,
+                mut meshes,
+                mut materials,
+                mut cache,
+                mut level,
+                assets_gltf,
+                mut despawn_snake_part_event,
+                mut snake_query,
+                mut box_query,
+                mut walls_query,
+            ) = state.get_mut(&mut app.world);
+
+            let mut part_builder = MaterialMeshBuilder {
+                meshes: meshes.as_mut(),
+                materials: materials.as_mut(),
+                cache: cache.as_mut(),
+            };
+
+            let respawner = EntityRespawner {
+                templates: &RespawnableEntities::default(),
+                library: &library,
+                assets_gltf: &assets_gltf,
+                catalog: &ModelCatalog::default(),
+            };
+
+            history.undo_last(
+                player,
+                &mut snake_query,
+                &mut box_query,
+                &mut walls_query,
+                &mut level,
+                &mut commands,
+                &mut part_builder,
+                &respawner,
+                &mut despawn_snake_part_event,
+            );
+
+            snakes.map(|entity| snake_query.get(entity).unwrap().1.head_position())
+        };
+
+        // The moves of the two snakes don't touch each other, the first one is undone alone.
+        assert_eq!(
+            undo(&mut history, Player::One),
+            [IVec3::new(1, 0, 0), IVec3::new(2, 0, 2)]
+        );
+        assert_eq!(history.last_player(), Some(Player::Two));
+
+        // The second player pushes the snake that the first player just moved.
+        history.push(
+            MoveHistoryEvent::PlayerSnakeMove(Player::One),
+            LevelGridEntity::new(snakes[0], EntityType::Snake),
+        );
+        history.push(
+            MoveHistoryEvent::PlayerSnakeMove(Player::Two),
+            LevelGridEntity::new(snakes[1], EntityType::Snake),
+        );
+        history.push(
+            MoveHistoryEvent::PassiveEntityMove(IVec3::ZERO),
+            LevelGridEntity::new(snakes[0], EntityType::Snake),
+        );
+        assert!(!history.can_undo(Player::One, IVec3::NEG_Y));
+
+        undo(&mut history, Player::One);
+        assert_eq!(history.move_history.len(), 5);
+
+        undo(&mut history, Player::Two);
+        undo(&mut history, Player::One);
+        assert_eq!(
+            undo(&mut history, Player::Two),
+            [IVec3::new(1, 0, 0), IVec3::new(1, 0, 2)]
+        );
+        assert!(history.move_history.is_empty());
+    }
+}
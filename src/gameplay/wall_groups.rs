@@ -0,0 +1,193 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{level::level_template::ModelId, library::GameAssets};
+
+use super::level_entities::{EntityType, GridEntity, LevelEntity, WallGroup};
+
+/// The edge of the cubes of cells merged together, smaller chunks are cheaper to rebuild.
+pub const WALL_CHUNK_SIZE: i32 = 8;
+
+const FACE_DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Two axes of each face with u x v pointing out, so the corners wind counterclockwise.
+const FACE_AXES: [(Vec3, Vec3); 6] = [
+    (Vec3::Y, Vec3::Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::Z, Vec3::X),
+    (Vec3::X, Vec3::Z),
+    (Vec3::X, Vec3::Y),
+    (Vec3::Y, Vec3::X),
+];
+
+pub struct WallGroupPlugin;
+
+impl Plugin for WallGroupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DirtyWallChunks>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                mark_changed_walls_system.before(rebuild_wall_groups_system),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, rebuild_wall_groups_system);
+    }
+}
+
+/// A wall drawn by the mesh of its group instead of its own, it still has its grid entity.
+#[derive(Component, Clone, Copy)]
+pub struct MergedWall;
+
+/// A default wall kept out of the groups and drawn with its own mesh.
+#[derive(Component, Clone, Copy)]
+pub struct UnmergedWall;
+
+/// The chunks whose group mesh is rebuilt at the end of the frame.
+#[derive(Resource, Default)]
+pub struct DirtyWallChunks(HashSet<IVec3>);
+
+impl DirtyWallChunks {
+    /// Rebuild the groups drawing the cell and its neighbours, for a wall added, moved or removed
+    /// at `position`.
+    pub fn mark(&mut self, position: IVec3) {
+        self.0.insert(wall_chunk(position));
+        for direction in FACE_DIRECTIONS {
+            self.0.insert(wall_chunk(position + direction));
+        }
+    }
+}
+
+pub fn wall_chunk(position: IVec3) -> IVec3 {
+    IVec3::new(
+        position.x.div_euclid(WALL_CHUNK_SIZE),
+        position.y.div_euclid(WALL_CHUNK_SIZE),
+        position.z.div_euclid(WALL_CHUNK_SIZE),
+    )
+}
+
+/// The faces of the walls that don't touch another default wall, in world space.
+fn build_wall_group_mesh(positions: &[IVec3], solid: &HashSet<IVec3>) -> Mesh {
+    let mut vertices: Vec<[f32; 3]> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+    let mut uvs: Vec<[f32; 2]> = vec![];
+    let mut indices: Vec<u32> = vec![];
+
+    for position in positions {
+        for (direction, (u, v)) in FACE_DIRECTIONS.into_iter().zip(FACE_AXES) {
+            if solid.contains(&(*position + direction)) {
+                continue;
+            }
+
+            let normal = direction.as_vec3();
+            let center = position.as_vec3() + normal * 0.5;
+            let first = vertices.len() as u32;
+            let corners = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
+            for (a, b) in corners {
+                vertices.push((center + u * a + v * b).to_array());
+                normals.push(normal.to_array());
+                uvs.push([a + 0.5, 0.5 - b]);
+            }
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn mark_changed_walls_system(
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    walls: Query<&GridEntity, Changed<GridEntity>>,
+) {
+    for grid_entity in &walls {
+        if grid_entity.entity_type == EntityType::Wall {
+            dirty_chunks.mark(grid_entity.position);
+        }
+    }
+}
+
+/// Replace the meshes of the default walls of the dirty chunks by one mesh per chunk.
+#[allow(clippy::type_complexity)]
+fn rebuild_wall_groups_system(
+    mut commands: Commands,
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    assets: Res<GameAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    walls: Query<
+        (
+            Entity,
+            &GridEntity,
+            Option<&MergedWall>,
+            Option<&UnmergedWall>,
+        ),
+        Without<ModelId>,
+    >,
+    groups: Query<(Entity, &WallGroup)>,
+) {
+    if dirty_chunks.0.is_empty() {
+        return;
+    }
+
+    let default_walls = walls
+        .iter()
+        .filter(|(_, grid_entity, _, _)| grid_entity.entity_type == EntityType::Wall);
+
+    let mut solid = HashSet::default();
+    let mut chunk_walls: HashMap<IVec3, Vec<IVec3>> = HashMap::default();
+
+    for (entity, grid_entity, merged, unmerged) in default_walls {
+        solid.insert(grid_entity.position);
+
+        let chunk = wall_chunk(grid_entity.position);
+        if unmerged.is_some() || !dirty_chunks.0.contains(&chunk) {
+            continue;
+        }
+
+        chunk_walls
+            .entry(chunk)
+            .or_default()
+            .push(grid_entity.position);
+        if merged.is_none() {
+            commands
+                .entity(entity)
+                .remove::<(Handle<Mesh>, Handle<StandardMaterial>)>()
+                .insert(MergedWall);
+        }
+    }
+
+    for (entity, group) in &groups {
+        if dirty_chunks.0.contains(&group.chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for (chunk, mut positions) in chunk_walls {
+        positions.sort_by_key(|position| position.to_array());
+
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(build_wall_group_mesh(&positions, &solid)),
+                material: assets.default_cube_material.clone(),
+                ..default()
+            },
+            WallGroup { chunk, positions },
+            LevelEntity,
+            Name::new("Wall Group"),
+        ));
+    }
+
+    dirty_chunks.0.clear();
+}
@@ -0,0 +1,601 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use bevy::prelude::*;
+use ron::{extensions::Extensions, ser::PrettyConfig, Options};
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::{level_entities::EntityType, snake_plugin::SnakeTemplate};
+
+use super::{
+    level_load_error::{ron_list_item_lines, LevelLoadError, LevelLoadErrorKind},
+    level_template::{DefaultModel, EntityTemplate, LevelTemplate, Model},
+};
+
+/// Extension of the text levels, where each layer of the level is drawn as a character grid.
+///
+/// The file starts with a RON header holding the legend and everything that is not drawn,
+/// followed by one `layer y=<y>` block per layer. Rows go along z and columns along x.
+/// Snake heads are drawn with an uppercase letter per snake, starting at `A`, and their bodies
+/// with the lowercase letter.
+pub const ASCII_LEVEL_EXTENSION: &str = "lvlmap";
+
+const LAYER_PREFIX: &str = "layer y=";
+const EMPTY_CELL: char = '.';
+
+/// Characters given to the legend entries that don't have a preferred one.
+const LEGEND_CHARACTERS: &str = "#%*^=_@!+&$~:;?<>{}[]()|/\\0123456789";
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+struct LegendEntry {
+    entity_type: EntityType,
+    model: Model,
+    #[serde(default)]
+    rotation: Quat,
+}
+
+impl From<&EntityTemplate> for LegendEntry {
+    fn from(entity: &EntityTemplate) -> Self {
+        Self {
+            entity_type: entity.entity_type,
+            model: entity.model.clone(),
+            rotation: entity.rotation,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct SnakeLayout {
+    /// The direction of each part, from the head.
+    directions: Vec<IVec3>,
+    /// The offset from each part to the next one, only written when the body drawn in the grid
+    /// can't be followed from the head.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<IVec3>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct AsciiLevelHeader {
+    /// The x and z of the first column and row.
+    origin: IVec2,
+    legend: BTreeMap<char, LegendEntry>,
+    snakes: Vec<SnakeLayout>,
+    /// Entities sharing a cell with another entity or a snake.
+    #[serde(default)]
+    extra_entities: Vec<EntityTemplate>,
+    /// The index in the level of each entity, the drawn ones in grid order then the extra ones.
+    /// Empty when the level lists them in that order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    order: Vec<usize>,
+    /// The ids of the drawn entities in grid order, missing ids are given after loading.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ids: Vec<u32>,
+    /// The rest of the level, without the drawn entities and snakes.
+    level: LevelTemplate,
+}
+
+/// The head and body letters of a snake, `None` when there are more snakes than letters.
+fn snake_characters(index: usize) -> Option<(char, char)> {
+    let letter = b'a'.checked_add(u8::try_from(index).ok()?)?;
+    letter
+        .is_ascii_lowercase()
+        .then(|| (letter.to_ascii_uppercase() as char, letter as char))
+}
+
+fn preferred_character(entry: &LegendEntry) -> Option<char> {
+    if entry.rotation != Quat::IDENTITY {
+        return None;
+    }
+
+    match entry.model {
+        Model::Default(DefaultModel::Wall) => Some('#'),
+        Model::Default(DefaultModel::BreakableWall) => Some('%'),
+        Model::Default(DefaultModel::Food) => Some('*'),
+        Model::Default(DefaultModel::Spike) => Some('^'),
+        Model::Default(DefaultModel::Box) => Some('='),
+        Model::Default(DefaultModel::Trigger) => Some('_'),
+        Model::Default(DefaultModel::Goal) => Some('@'),
+        Model::Default(DefaultModel::GravitySwitch) => Some('!'),
+        Model::Asset(_) => None,
+    }
+}
+
+/// Follow the body of a snake from its head. The next part is behind the current one when the
+/// body continues there, else the only free neighbour with the body letter.
+fn trace_snake(
+    cells: &HashMap<IVec3, char>,
+    index: usize,
+    head: IVec3,
+    directions: &[IVec3],
+) -> Option<Vec<IVec3>> {
+    let (_, body) = snake_characters(index)?;
+    let mut positions = vec![head];
+
+    // The direction of a part points from the part behind it, the tail has nothing behind.
+    for direction in &directions[..directions.len().saturating_sub(1)] {
+        let previous = *positions.last().unwrap();
+        let is_free_body =
+            |position: IVec3| cells.get(&position) == Some(&body) && !positions.contains(&position);
+
+        let behind = previous - *direction;
+        let next = if is_free_body(behind) {
+            behind
+        } else {
+            let mut candidates = NEIGHBOURS
+                .iter()
+                .map(|offset| previous + *offset)
+                .filter(|position| is_free_body(*position));
+
+            let candidate = candidates.next()?;
+            if candidates.next().is_some() {
+                return None;
+            }
+            candidate
+        };
+
+        positions.push(next);
+    }
+
+    Some(positions)
+}
+
+/// Write the level in the text format, fails when there are more snakes than letters.
+pub fn level_to_ascii(template: &LevelTemplate) -> io::Result<String> {
+    let mut cells: HashMap<IVec3, char> = HashMap::new();
+
+    // Snakes are drawn first, so they always get their cells.
+    for (index, snake) in template.snakes.iter().enumerate() {
+        let (head, body) = snake_characters(index).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the level has more snakes than letters",
+            )
+        })?;
+        for (part_index, (position, _)) in snake.iter().enumerate() {
+            cells
+                .entry(*position)
+                .or_insert(if part_index == 0 { head } else { body });
+        }
+    }
+
+    let mut legend: BTreeMap<char, LegendEntry> = BTreeMap::new();
+    let mut extra_entities = vec![];
+    let mut drawn_indices: Vec<(IVec3, usize)> = vec![];
+    let mut extra_indices = vec![];
+
+    for (index, entity) in template.entities.iter().enumerate() {
+        let entry = LegendEntry::from(entity);
+
+        let character = legend
+            .iter()
+            .find(|(_, legend_entry)| **legend_entry == entry)
+            .map(|(character, _)| *character)
+            .or_else(|| {
+                let character = preferred_character(&entry)
+                    .filter(|character| !legend.contains_key(character))
+                    .or_else(|| {
+                        LEGEND_CHARACTERS
+                            .chars()
+                            .find(|character| !legend.contains_key(character))
+                    })?;
+
+                legend.insert(character, entry);
+                Some(character)
+            });
+
+        // Entities with properties are kept whole in the header, the cells only give a type.
+        match character {
+            Some(character)
+                if entity.properties.is_empty() && !cells.contains_key(&entity.grid_position) =>
+            {
+                cells.insert(entity.grid_position, character);
+                drawn_indices.push((entity.grid_position, index));
+            }
+            _ => {
+                extra_entities.push(entity.clone());
+                extra_indices.push(index);
+            }
+        }
+    }
+
+    // The grid is read layer by layer, then row by row.
+    drawn_indices.sort_by_key(|(position, _)| (position.y, position.z, position.x));
+    let order: Vec<usize> = drawn_indices
+        .iter()
+        .map(|(_, index)| *index)
+        .chain(extra_indices)
+        .collect();
+    let in_order = order.iter().enumerate().all(|(read, index)| read == *index);
+
+    let ids: Vec<u32> = drawn_indices
+        .iter()
+        .map(|(_, index)| template.entities[*index].id)
+        .collect();
+
+    let snakes = template
+        .snakes
+        .iter()
+        .enumerate()
+        .map(|(index, snake)| {
+            let positions: Vec<IVec3> = snake.iter().map(|(position, _)| *position).collect();
+            let directions: Vec<IVec3> = snake.iter().map(|(_, direction)| *direction).collect();
+
+            let steps = if trace_snake(&cells, index, positions[0], &directions).as_ref()
+                == Some(&positions)
+            {
+                vec![]
+            } else {
+                positions
+                    .windows(2)
+                    .map(|parts| parts[1] - parts[0])
+                    .collect()
+            };
+
+            SnakeLayout { directions, steps }
+        })
+        .collect();
+
+    let min = cells
+        .keys()
+        .fold(IVec3::splat(i32::MAX), |min, cell| min.min(*cell));
+    let max = cells
+        .keys()
+        .fold(IVec3::splat(i32::MIN), |max, cell| max.max(*cell));
+
+    let header = AsciiLevelHeader {
+        origin: if cells.is_empty() {
+            IVec2::ZERO
+        } else {
+            IVec2::new(min.x, min.z)
+        },
+        legend,
+        snakes,
+        extra_entities,
+        order: if in_order { vec![] } else { order },
+        ids: if ids.iter().all(|id| *id == 0) {
+            vec![]
+        } else {
+            ids
+        },
+        level: LevelTemplate {
+            snakes: vec![],
+            entities: vec![],
+            ..template.clone()
+        },
+    };
+
+    let mut text = ron::ser::to_string_pretty(&header, PrettyConfig::default()).unwrap();
+    text.push('\n');
+
+    if cells.is_empty() {
+        return Ok(text);
+    }
+
+    for y in min.y..=max.y {
+        text.push_str(&format!("{}{}\n", LAYER_PREFIX, y));
+
+        for z in min.z..=max.z {
+            let row: String = (min.x..=max.x)
+                .map(|x| {
+                    cells
+                        .get(&IVec3::new(x, y, z))
+                        .copied()
+                        .unwrap_or(EMPTY_CELL)
+                })
+                .collect();
+
+            text.push_str(&row);
+            text.push('\n');
+        }
+    }
+
+    Ok(text)
+}
+
+/// Read a level in the text format, `file` is only used to report errors.
+pub fn level_from_ascii(text: &str, file: &str) -> Result<LevelTemplate, LevelLoadError> {
+    let grid_start = text
+        .find(&format!("\n{}", LAYER_PREFIX))
+        .map_or(text.len(), |index| index + 1);
+
+    // Maps written before the background color was optional have it without `Some`.
+    let header: AsciiLevelHeader = Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_str(&text[..grid_start])
+        .map_err(|error| LevelLoadError::parse(file, &error))?;
+
+    let header_lines = text[..grid_start].lines().count();
+    let parse_error = |line: usize, message: String| {
+        LevelLoadError::new(file, LevelLoadErrorKind::Parse(message)).at_line(line)
+    };
+
+    let mut cells: HashMap<IVec3, char> = HashMap::new();
+    let mut heads: HashMap<usize, IVec3> = HashMap::new();
+    let mut entities = vec![];
+    let mut entity_lines = vec![];
+
+    // The y of the current layer and the z of the next row.
+    let mut layer: Option<(i32, i32)> = None;
+
+    for (line_index, line) in text[grid_start..].lines().enumerate() {
+        let line_number = header_lines + line_index + 1;
+
+        if let Some(y) = line.strip_prefix(LAYER_PREFIX) {
+            let y = y
+                .trim()
+                .parse::<i32>()
+                .map_err(|_| parse_error(line_number, format!("invalid layer {}", y)))?;
+
+            layer = Some((y, header.origin.y));
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some((y, z)) = layer.as_mut() else {
+            return Err(parse_error(
+                line_number,
+                "row outside of a layer".to_owned(),
+            ));
+        };
+
+        for (column, character) in line.chars().enumerate() {
+            let position = IVec3::new(header.origin.x + column as i32, *y, *z);
+
+            if character == EMPTY_CELL || character == ' ' {
+                continue;
+            }
+
+            if character.is_ascii_uppercase() {
+                let index = (character.to_ascii_lowercase() as u8 - b'a') as usize;
+                if heads.insert(index, position).is_some() {
+                    return Err(parse_error(
+                        line_number,
+                        format!("snake {} has two heads", character),
+                    ));
+                }
+            } else if character.is_ascii_lowercase() {
+                cells.insert(position, character);
+            } else if let Some(entry) = header.legend.get(&character) {
+                entities.push(EntityTemplate {
+                    entity_type: entry.entity_type,
+                    model: entry.model.clone(),
+                    grid_position: position,
+                    rotation: entry.rotation,
+                    ..default()
+                });
+                entity_lines.push(line_number);
+            } else {
+                return Err(parse_error(
+                    line_number,
+                    format!("'{}' is not in the legend", character),
+                ));
+            }
+        }
+
+        *z += 1;
+    }
+
+    let snake_error = |head: char, message: &str| {
+        LevelLoadError::new(
+            file,
+            LevelLoadErrorKind::Parse(format!("snake {} {}", head, message)),
+        )
+    };
+
+    let mut snakes: Vec<SnakeTemplate> = Vec::with_capacity(header.snakes.len());
+    for (index, layout) in header.snakes.iter().enumerate() {
+        let Some((letter, _)) = snake_characters(index) else {
+            return Err(LevelLoadError::new(
+                file,
+                LevelLoadErrorKind::Parse("more snakes than letters".to_owned()),
+            ));
+        };
+
+        let Some(head) = heads.get(&index) else {
+            return Err(snake_error(letter, "has no head"));
+        };
+
+        let positions = if layout.steps.is_empty() {
+            trace_snake(&cells, index, *head, &layout.directions)
+                .ok_or_else(|| snake_error(letter, "has a body that can't be followed"))?
+        } else {
+            if layout.steps.len() + 1 != layout.directions.len() {
+                return Err(snake_error(letter, "needs one step less than directions"));
+            }
+
+            layout
+                .steps
+                .iter()
+                .fold(vec![*head], |mut positions, step| {
+                    positions.push(*positions.last().unwrap() + *step);
+                    positions
+                })
+        };
+
+        snakes.push(
+            positions
+                .into_iter()
+                .zip(layout.directions.iter().copied())
+                .collect(),
+        );
+    }
+
+    if !header.ids.is_empty() {
+        if header.ids.len() != entities.len() {
+            return Err(LevelLoadError::new(
+                file,
+                LevelLoadErrorKind::Parse(format!(
+                    "{} ids for {} drawn entities",
+                    header.ids.len(),
+                    entities.len()
+                )),
+            ));
+        }
+
+        for (entity, id) in entities.iter_mut().zip(&header.ids) {
+            entity.id = *id;
+        }
+    }
+
+    let extra_lines = ron_list_item_lines(&text[..grid_start], "extra_entities");
+    if extra_lines.len() == header.extra_entities.len() {
+        entity_lines.extend(extra_lines);
+    }
+    entities.extend(header.extra_entities);
+
+    if !header.order.is_empty() {
+        (entities, entity_lines) = restore_order(entities, entity_lines, &header.order)
+            .ok_or_else(|| {
+                LevelLoadError::new(
+                    file,
+                    LevelLoadErrorKind::Parse("order doesn't list every entity once".to_owned()),
+                )
+            })?;
+    }
+
+    let mut template = LevelTemplate {
+        snakes,
+        entities,
+        entity_lines,
+        ..header.level
+    };
+
+    // Maps written by hand can leave the ids out.
+    template.assign_missing_ids();
+    Ok(template)
+}
+
+/// Put the entities read from the file back at their index in the level.
+fn restore_order(
+    entities: Vec<EntityTemplate>,
+    lines: Vec<usize>,
+    order: &[usize],
+) -> Option<(Vec<EntityTemplate>, Vec<usize>)> {
+    if order.len() != entities.len() {
+        return None;
+    }
+
+    let mut ordered = vec![None; entities.len()];
+    for (entity, index) in entities.into_iter().zip(order) {
+        let slot = ordered.get_mut(*index)?;
+        if slot.is_some() {
+            return None;
+        }
+        *slot = Some(entity);
+    }
+
+    // Lines are only kept when every entity has one.
+    let mut ordered_lines = vec![];
+    if lines.len() == order.len() {
+        ordered_lines = vec![0; lines.len()];
+        for (line, index) in lines.into_iter().zip(order) {
+            ordered_lines[*index] = line;
+        }
+    }
+
+    ordered
+        .into_iter()
+        .collect::<Option<_>>()
+        .map(|entities| (entities, ordered_lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::level_template::{parse_level, PropertyValue};
+
+    fn entity_fields(template: &LevelTemplate) -> Vec<(EntityType, Model, IVec3, Quat)> {
+        template
+            .entities
+            .iter()
+            .map(|entity| {
+                (
+                    entity.entity_type,
+                    entity.model.clone(),
+                    entity.grid_position,
+                    entity.rotation,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levels_keep_their_entities_through_the_text_format() {
+        let template = parse_level(
+            include_bytes!("../../assets/levels/level4.lvl"),
+            "levels/level4.lvl",
+        )
+        .unwrap();
+
+        let text = level_to_ascii(&template).unwrap();
+        let read = parse_level(text.as_bytes(), "levels/level4.lvlmap").unwrap();
+
+        assert_eq!(read.snakes, template.snakes);
+        assert_eq!(entity_fields(&read), entity_fields(&template));
+        assert_eq!(read.gravity, template.gravity);
+    }
+
+    #[test]
+    fn entities_keep_their_ids_and_references() {
+        let entity = |id, entity_type, x| EntityTemplate {
+            id,
+            entity_type,
+            grid_position: IVec3::new(x, 0, 0),
+            ..default()
+        };
+
+        let mut trigger = entity(3, EntityType::Trigger, 0);
+        trigger
+            .properties
+            .insert("target".to_owned(), PropertyValue::Entity(7));
+
+        let template = LevelTemplate {
+            entities: vec![
+                entity(5, EntityType::Wall, 2),
+                trigger,
+                entity(7, EntityType::Box, 1),
+                entity(1, EntityType::Wall, 3),
+            ],
+            ..default()
+        };
+
+        let text = level_to_ascii(&template).unwrap();
+        let read = parse_level(text.as_bytes(), "levels/ids.lvlmap").unwrap();
+
+        let ids_and_properties = |template: &LevelTemplate| {
+            template
+                .entities
+                .iter()
+                .map(|entity| (entity.id, entity.properties.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids_and_properties(&read), ids_and_properties(&template));
+        assert_eq!(entity_fields(&read), entity_fields(&template));
+    }
+
+    #[test]
+    fn snakes_past_the_last_letter_are_refused() {
+        let template = LevelTemplate {
+            snakes: (0..27)
+                .map(|index| vec![(IVec3::new(index, 0, 0), IVec3::X)])
+                .collect(),
+            ..default()
+        };
+
+        assert!(level_to_ascii(&template).is_err());
+    }
+}
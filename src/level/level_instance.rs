@@ -17,7 +17,9 @@ impl EntityType {
     }
 
     pub fn is_traversable(&self) -> bool {
-        *self == EntityType::Goal || *self == EntityType::Trigger
+        *self == EntityType::Goal
+            || *self == EntityType::Trigger
+            || *self == EntityType::GravitySwitch
     }
 }
 
@@ -47,15 +49,41 @@ impl LevelGridEntity {
 #[derive(Resource)]
 pub struct LevelInstance {
     occupied_cells: HashMap<IVec3, LevelGridEntity>,
+    gravity: IVec3,
+    planar: bool,
 }
 
 impl LevelInstance {
     pub fn new() -> Self {
         LevelInstance {
             occupied_cells: HashMap::new(),
+            gravity: IVec3::NEG_Y,
+            planar: false,
         }
     }
 
+    /// The direction in which things fall, a unit vector along one of the axes.
+    pub fn gravity(&self) -> IVec3 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: IVec3) {
+        self.gravity = gravity;
+    }
+
+    pub fn is_planar(&self) -> bool {
+        self.planar
+    }
+
+    pub fn set_planar(&mut self, planar: bool) {
+        self.planar = planar;
+    }
+
+    /// Planar levels don't allow moves along z.
+    pub fn allows_direction(&self, direction: IVec3) -> bool {
+        !self.planar || direction.z == 0
+    }
+
     pub fn is_empty(&self, position: IVec3) -> bool {
         !self.occupied_cells.contains_key(&position)
     }
@@ -251,16 +279,16 @@ impl LevelInstance {
 
         const ARBITRARY_HIGH_DISTANCE: i32 = 50;
 
-        let mut current_position = position + IVec3::NEG_Y;
+        let mut current_position = position + self.gravity;
         while self.is_empty_or_spike(current_position)
             || self.is_goal(current_position)
             || self.is_entity(current_position, snake_entity)
         {
-            current_position += IVec3::NEG_Y;
+            current_position += self.gravity;
             distance += 1;
 
             // There is no ground below.
-            if current_position.y <= 0 {
+            if distance >= ARBITRARY_HIGH_DISTANCE {
                 return ARBITRARY_HIGH_DISTANCE;
             }
         }
@@ -268,6 +296,15 @@ impl LevelInstance {
         distance
     }
 
+    /// Check if a position is further than `margin` cells past the bottom of the level along gravity.
+    pub fn is_below_level(&self, position: IVec3, margin: i32) -> bool {
+        self.occupied_cells
+            .keys()
+            .map(|cell| cell.dot(self.gravity))
+            .max()
+            .map_or(false, |bottom| position.dot(self.gravity) > bottom + margin)
+    }
+
     pub fn find_first_free_cell_on_ray(&self, ray: Ray) -> Option<IVec3> {
         let aabb = self.compute_bounds();
 
@@ -276,7 +313,11 @@ impl LevelInstance {
         let bound_min: Vec3 = (aabb.min() - Vec3A::ONE).into();
         let bound_max: Vec3 = (aabb.max() + Vec3A::ONE).into();
 
-        let Some([t_min, t_max]) = ray_intersects_aabb(ray, &Aabb::from_min_max(bound_min, bound_max), &Mat4::IDENTITY) else {
+        let Some([t_min, t_max]) = ray_intersects_aabb(
+            ray,
+            &Aabb::from_min_max(bound_min, bound_max),
+            &Mat4::IDENTITY,
+        ) else {
             return None;
         };
 
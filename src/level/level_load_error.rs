@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+
+use crate::gameplay::level_entities::EntityType;
+
+use super::{
+    level_template::{LevelTemplate, PropertyValue, MAX_DIFFICULTY},
+    migrations::CURRENT_LEVEL_VERSION,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelLoadErrorKind {
+    /// The file is not valid RON or doesn't match the level schema.
+    Parse(String),
+    /// The asset server failed to load the file.
+    Unreadable,
+    /// Snakes are listed in `snakes`, not in the entities.
+    SnakeEntity,
+    /// The model is not in the models folder.
+    MissingModel(String),
+    /// The model catalog doesn't allow the model for the entity type.
+    ModelNotAllowed(String, EntityType),
+    /// The model covers more than one cell, but the entity moves or gets removed.
+    MultiCellModel(String, EntityType),
+    /// Two entities have the same id.
+    DuplicateEntityId(u32),
+    /// A property refers to an id that no entity has.
+    UnknownEntityId(String, u32),
+    /// The file was saved by a newer version of the game.
+    UnsupportedVersion(u32),
+    /// The difficulty in the metadata is not between 1 and `MAX_DIFFICULTY`.
+    InvalidDifficulty(u8),
+}
+
+impl fmt::Display for LevelLoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelLoadErrorKind::Parse(message) => write!(f, "{}", message),
+            LevelLoadErrorKind::Unreadable => write!(f, "the file could not be loaded"),
+            LevelLoadErrorKind::SnakeEntity => {
+                write!(f, "snakes can't be entities, move it to the snakes list")
+            }
+            LevelLoadErrorKind::MissingModel(path) => write!(f, "unknown model {}", path),
+            LevelLoadErrorKind::ModelNotAllowed(path, entity_type) => {
+                write!(
+                    f,
+                    "model {} can't be used for {:?} entities",
+                    path, entity_type
+                )
+            }
+            LevelLoadErrorKind::MultiCellModel(path, entity_type) => {
+                write!(
+                    f,
+                    "model {} covers more than one cell, {:?} entities need a single one",
+                    path, entity_type
+                )
+            }
+            LevelLoadErrorKind::DuplicateEntityId(id) => {
+                write!(f, "another entity already has the id {}", id)
+            }
+            LevelLoadErrorKind::UnknownEntityId(property, id) => {
+                write!(
+                    f,
+                    "property {} refers to the unknown entity {}",
+                    property, id
+                )
+            }
+            LevelLoadErrorKind::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "version {} is newer than the supported version {}",
+                    version, CURRENT_LEVEL_VERSION
+                )
+            }
+            LevelLoadErrorKind::InvalidDifficulty(difficulty) => {
+                write!(
+                    f,
+                    "difficulty {} is not between 1 and {}",
+                    difficulty, MAX_DIFFICULTY
+                )
+            }
+        }
+    }
+}
+
+/// Why a level could not be loaded, with where in the file the problem is.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct LevelLoadError {
+    pub file: String,
+    pub line: Option<usize>,
+    /// Index of the entity in the entities list.
+    pub entity: Option<usize>,
+    pub kind: LevelLoadErrorKind,
+}
+
+impl LevelLoadError {
+    pub fn new(file: &str, kind: LevelLoadErrorKind) -> Self {
+        Self {
+            file: file.to_owned(),
+            line: None,
+            entity: None,
+            kind,
+        }
+    }
+
+    pub fn parse(file: &str, error: &ron::error::SpannedError) -> Self {
+        Self {
+            line: Some(error.position.line),
+            ..Self::new(file, LevelLoadErrorKind::Parse(error.code.to_string()))
+        }
+    }
+
+    pub fn at_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Point the error at an entity of the template, with its line when the file recorded it.
+    pub fn with_entity(mut self, template: &LevelTemplate, index: usize) -> Self {
+        self.entity = Some(index);
+        self.line = template.entity_lines.get(index).copied().or(self.line);
+        self
+    }
+}
+
+impl fmt::Display for LevelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+
+        if let Some(entity) = self.entity {
+            write!(f, " (entity {})", entity)?;
+        }
+
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for LevelLoadError {}
+
+/// The errors of the level files that failed to load, keyed by asset path.
+/// Shared with the asset loader since the asset server only reports the failure.
+#[derive(Resource, Clone, Default)]
+pub struct LevelLoadErrors(Arc<Mutex<HashMap<String, LevelLoadError>>>);
+
+impl LevelLoadErrors {
+    pub fn insert(&self, error: LevelLoadError) {
+        self.0.lock().unwrap().insert(error.file.clone(), error);
+    }
+
+    pub fn take(&self, file: &str) -> Option<LevelLoadError> {
+        self.0.lock().unwrap().remove(file)
+    }
+}
+
+/// Check the parts of the template that don't depend on the game assets.
+pub fn validate_template(template: &LevelTemplate, file: &str) -> Result<(), LevelLoadError> {
+    if let Some(difficulty) = template.metadata.difficulty {
+        if !(1..=MAX_DIFFICULTY).contains(&difficulty) {
+            return Err(LevelLoadError::new(
+                file,
+                LevelLoadErrorKind::InvalidDifficulty(difficulty),
+            ));
+        }
+    }
+
+    let mut ids = HashSet::new();
+
+    for (index, entity) in template.entities.iter().enumerate() {
+        if entity.entity_type == EntityType::Snake {
+            return Err(LevelLoadError::new(file, LevelLoadErrorKind::SnakeEntity)
+                .with_entity(template, index));
+        }
+
+        if !ids.insert(entity.id) {
+            return Err(LevelLoadError::new(
+                file,
+                LevelLoadErrorKind::DuplicateEntityId(entity.id),
+            )
+            .with_entity(template, index));
+        }
+    }
+
+    for (index, entity) in template.entities.iter().enumerate() {
+        for (key, value) in &entity.properties {
+            if let PropertyValue::Entity(id) = value {
+                if !ids.contains(id) {
+                    let kind = LevelLoadErrorKind::UnknownEntityId(key.clone(), *id);
+                    return Err(LevelLoadError::new(file, kind).with_entity(template, index));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The line where each item of the list in the field `key` of the outer RON struct starts.
+/// Empty when the field is not a list.
+pub fn ron_list_item_lines(text: &str, key: &str) -> Vec<usize> {
+    let mut lines = vec![];
+    let mut line = 1;
+    let mut depth = 0;
+    let mut identifier = String::new();
+    let mut field = String::new();
+    let mut in_list = false;
+    let mut expect_item = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(character) = chars.next() {
+        let starts_token = !character.is_whitespace() && character != ',';
+        let in_comment = character == '/' && matches!(chars.peek(), Some('/') | Some('*'));
+        if expect_item && starts_token && !in_comment && !matches!(character, ')' | ']' | '}') {
+            lines.push(line);
+            expect_item = false;
+        }
+
+        match character {
+            '\n' => line += 1,
+            '/' if chars.peek() == Some(&'/') => {
+                for character in chars.by_ref() {
+                    if character == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for character in chars.by_ref() {
+                    if character == '\n' {
+                        line += 1;
+                    }
+                    if previous == '*' && character == '/' {
+                        break;
+                    }
+                    previous = character;
+                }
+            }
+            '"' | '\'' => {
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        line += 1;
+                    }
+                    if !escaped && next == character {
+                        break;
+                    }
+                    escaped = !escaped && next == '\\';
+                }
+            }
+            '(' | '[' | '{' => {
+                depth += 1;
+                if character == '[' && depth == 2 && field == key {
+                    in_list = true;
+                    expect_item = true;
+                }
+            }
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if in_list && depth == 1 {
+                    return lines;
+                }
+            }
+            ',' if in_list && depth == 2 => expect_item = true,
+            ',' if depth == 1 => {
+                field.clear();
+                identifier.clear();
+            }
+            ':' if depth == 1 => field = std::mem::take(&mut identifier),
+            character if depth == 1 && (character.is_alphanumeric() || character == '_') => {
+                identifier.push(character)
+            }
+            character if depth == 1 && !character.is_whitespace() => identifier.clear(),
+            _ => {}
+        }
+    }
+
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_items_start_at_their_line() {
+        let text = "(\n    version: 2,\n    // entities: [\n    entities: [\n        (id: 1, \
+                    properties: {\"a\": Text(\"),\")}),\n\n        (\n            id: 2,\n        \
+                    ),\n    ],\n)\n";
+
+        assert_eq!(ron_list_item_lines(text, "entities"), vec![5, 7]);
+        assert!(ron_list_item_lines(text, "snakes").is_empty());
+    }
+
+    #[test]
+    fn difficulty_must_be_in_range() {
+        let mut template = LevelTemplate::default();
+
+        for difficulty in [None, Some(1), Some(MAX_DIFFICULTY)] {
+            template.metadata.difficulty = difficulty;
+            assert_eq!(validate_template(&template, "level.lvl"), Ok(()));
+        }
+
+        for difficulty in [0, MAX_DIFFICULTY + 1] {
+            template.metadata.difficulty = Some(difficulty);
+            assert_eq!(
+                validate_template(&template, "level.lvl").map_err(|error| error.kind),
+                Err(LevelLoadErrorKind::InvalidDifficulty(difficulty))
+            );
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{gameplay::score_plugin::Progress, library::AssetLibrary};
+
+/// The pack played when none is given on the command line.
+pub const DEFAULT_PACK: &str = "packs/main.pack";
+
+/// What the player needs to do before playing the levels of a world.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum UnlockRequirement {
+    #[default]
+    Always,
+    /// The total number of stars earned in the pack.
+    Stars(u32),
+    /// Every level of the world at this index is completed.
+    CompletedWorld(usize),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PackWorld {
+    pub name: String,
+    #[serde(default)]
+    pub unlock: UnlockRequirement,
+    /// Asset paths of the levels, in the order they are played.
+    pub levels: Vec<String>,
+}
+
+/// An ordered list of levels grouped in worlds, read from the `.pack` files of the packs folder.
+/// Levels are referred to by their index in the whole pack.
+#[derive(Deserialize, Serialize, TypeUuid, Debug, Clone)]
+#[uuid = "9c3f5b8e-3d0a-4f49-a3c1-6f1b2d7e8a40"]
+pub struct LevelPack {
+    pub name: String,
+    pub worlds: Vec<PackWorld>,
+    /// Asset path of the theme of the levels that don't pick one.
+    #[serde(default)]
+    pub theme: Option<String>,
+}
+
+impl LevelPack {
+    pub fn levels(&self) -> impl Iterator<Item = &String> {
+        self.worlds.iter().flat_map(|world| world.levels.iter())
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.worlds.iter().map(|world| world.levels.len()).sum()
+    }
+
+    pub fn level(&self, index: usize) -> Option<&str> {
+        self.levels().nth(index).map(String::as_str)
+    }
+
+    /// The index of the world holding the level.
+    pub fn world_of(&self, index: usize) -> Option<usize> {
+        let mut first_level = 0;
+        for (world_index, world) in self.worlds.iter().enumerate() {
+            if index < first_level + world.levels.len() {
+                return Some(world_index);
+            }
+            first_level += world.levels.len();
+        }
+
+        None
+    }
+
+    pub fn stars(&self, progress: &Progress) -> u32 {
+        self.levels()
+            .filter_map(|level| progress.stars(level))
+            .map(u32::from)
+            .sum()
+    }
+
+    pub fn is_world_unlocked(&self, world_index: usize, progress: &Progress) -> bool {
+        let Some(world) = self.worlds.get(world_index) else {
+            return false;
+        };
+
+        match &world.unlock {
+            UnlockRequirement::Always => true,
+            UnlockRequirement::Stars(stars) => self.stars(progress) >= *stars,
+            UnlockRequirement::CompletedWorld(index) => self.is_world_completed(*index, progress),
+        }
+    }
+
+    pub fn is_world_completed(&self, world_index: usize, progress: &Progress) -> bool {
+        self.worlds.get(world_index).map_or(false, |world| {
+            world
+                .levels
+                .iter()
+                .all(|level| progress.stars(level).is_some())
+        })
+    }
+
+    pub fn is_level_unlocked(&self, index: usize, progress: &Progress) -> bool {
+        self.world_of(index).map_or(false, |world_index| {
+            self.is_world_unlocked(world_index, progress)
+        })
+    }
+}
+
+/// The asset path of the pack used by the select menu and the next level logic.
+#[derive(Resource, Clone, Debug)]
+pub struct ActivePack(pub String);
+
+impl Default for ActivePack {
+    fn default() -> Self {
+        Self(DEFAULT_PACK.to_owned())
+    }
+}
+
+impl ActivePack {
+    /// The pack, once it is loaded.
+    pub fn get<'a>(
+        &self,
+        library: &AssetLibrary,
+        packs: &'a Assets<LevelPack>,
+    ) -> Option<&'a LevelPack> {
+        library
+            .packs
+            .get(&self.0)
+            .and_then(|handle| packs.get(handle))
+    }
+}
+
+#[derive(Default)]
+pub struct LevelPackLoader;
+
+impl AssetLoader for LevelPackLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let pack = ron::de::from_bytes::<LevelPack>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(pack));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pack"]
+    }
+}
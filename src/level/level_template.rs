@@ -1,3 +1,8 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
 use bevy::{
     asset::{AssetLoader, LoadContext, LoadedAsset},
     gltf::Gltf,
@@ -8,28 +13,44 @@ use bevy::{
 
 use serde::{Deserialize, Serialize};
 
-use crate::gameplay::{level_entities::EntityType, snake_plugin::SnakeTemplate};
+use crate::gameplay::{
+    level_entities::EntityType, npc_plugin::NpcSnakeTemplate, snake_plugin::SnakeTemplate,
+};
+
+use super::{
+    ascii_level::{level_from_ascii, ASCII_LEVEL_EXTENSION},
+    level_load_error::{
+        ron_list_item_lines, validate_template, LevelLoadError, LevelLoadErrorKind, LevelLoadErrors,
+    },
+    migrations::{load_level_template, migrate, unversioned, CURRENT_LEVEL_VERSION},
+};
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DefaultModel {
     Food,
     Spike,
     Wall,
+    BreakableWall,
     Box,
     Trigger,
     Goal,
+    GravitySwitch,
 }
 
-impl From<EntityType> for DefaultModel {
-    fn from(value: EntityType) -> Self {
+impl TryFrom<EntityType> for DefaultModel {
+    type Error = LevelLoadErrorKind;
+
+    fn try_from(value: EntityType) -> Result<Self, Self::Error> {
         match value {
-            EntityType::Food => DefaultModel::Food,
-            EntityType::Spike => DefaultModel::Spike,
-            EntityType::Wall => DefaultModel::Wall,
-            EntityType::Box => DefaultModel::Box,
-            EntityType::Trigger => DefaultModel::Trigger,
-            EntityType::Snake => todo!(),
-            EntityType::Goal => DefaultModel::Goal,
+            EntityType::Food => Ok(DefaultModel::Food),
+            EntityType::Spike => Ok(DefaultModel::Spike),
+            EntityType::Wall => Ok(DefaultModel::Wall),
+            EntityType::BreakableWall => Ok(DefaultModel::BreakableWall),
+            EntityType::Box => Ok(DefaultModel::Box),
+            EntityType::Trigger => Ok(DefaultModel::Trigger),
+            EntityType::Snake => Err(LevelLoadErrorKind::SnakeEntity),
+            EntityType::Goal => Ok(DefaultModel::Goal),
+            EntityType::GravitySwitch => Ok(DefaultModel::GravitySwitch),
         }
     }
 }
@@ -39,37 +60,197 @@ pub struct ModelId {
     pub source_asset: Handle<Gltf>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub enum Model {
     Default(DefaultModel),
     Asset(String),
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// A setting of an entity, read by the gameplay plugins.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Int(i64),
+    String(String),
+    Vector(IVec3),
+    /// The id of another entity of the level.
+    Entity(u32),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct EntityTemplate {
+    /// Unique in the level, so other entities can refer to it. Zero until one is assigned.
+    #[serde(default)]
+    pub id: u32,
     pub entity_type: EntityType,
     pub model: Model,
     pub grid_position: IVec3,
     #[serde(default)]
     pub rotation: Quat,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, PropertyValue>,
 }
 
 impl Default for EntityTemplate {
     fn default() -> Self {
         Self {
+            id: 0,
             entity_type: EntityType::Wall,
             model: Model::Default(DefaultModel::Wall),
             grid_position: Default::default(),
             rotation: Default::default(),
+            properties: Default::default(),
         }
     }
 }
 
-#[derive(Resource, Deserialize, Serialize, TypeUuid, Debug, Default)]
+fn default_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+/// A prop placed freely in the level, it is only drawn and never takes part in the grid.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DecorationTemplate {
+    /// Path of the model in the models folder.
+    pub model: String,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+    #[serde(default = "default_scale")]
+    pub scale: Vec3,
+}
+
+impl DecorationTemplate {
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.translation,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+}
+
+/// The difficulty of the hardest levels, the easiest ones are 1.
+pub const MAX_DIFFICULTY: u8 = 5;
+
+/// Describes the level in the menus and during play, every field is optional.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LevelMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// From 1 for the easiest levels to `MAX_DIFFICULTY`.
+    pub difficulty: Option<u8>,
+    pub tags: Vec<String>,
+    /// Shown one after the other when the player asks for help.
+    pub hints: Vec<String>,
+}
+
+/// The sun, ambient light and background of a level. The defaults give the look levels had
+/// before they could change it.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LevelLighting {
+    /// The direction the sun light travels in, its shadows always cover the whole level.
+    pub direction: Vec3,
+    pub color: Color,
+    /// In lux.
+    pub illuminance: f32,
+    pub ambient_color: Color,
+    pub ambient_brightness: f32,
+    /// The clear color of the camera, the background of the theme when not set.
+    pub background_color: Option<Color>,
+}
+
+impl Default for LevelLighting {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(-0.5, -3.0, -0.5).normalize(),
+            color: Color::WHITE,
+            illuminance: 10000.0,
+            ambient_color: Color::WHITE,
+            ambient_brightness: 0.05,
+            background_color: None,
+        }
+    }
+}
+
+fn default_gravity() -> IVec3 {
+    IVec3::NEG_Y
+}
+
+#[derive(Resource, Deserialize, Serialize, TypeUuid, Debug, Clone)]
 #[uuid = "39cadc56-aa9c-4543-8640-a018b74b5052"]
 pub struct LevelTemplate {
+    /// The schema version of the file, older files are migrated when loaded.
+    #[serde(default = "unversioned")]
+    pub version: u32,
     pub snakes: Vec<SnakeTemplate>,
     pub entities: Vec<EntityTemplate>,
+    #[serde(default = "default_gravity")]
+    pub gravity: IVec3,
+    #[serde(default)]
+    pub npc_snakes: Vec<NpcSnakeTemplate>,
+    /// The number of player moves of the best known solution.
+    #[serde(default)]
+    pub par: Option<u32>,
+    /// Snakes only move in the x/y plane, for levels made from 2D puzzles.
+    #[serde(default)]
+    pub planar: bool,
+    #[serde(default)]
+    pub metadata: LevelMetadata,
+    #[serde(default)]
+    pub decorations: Vec<DecorationTemplate>,
+    #[serde(default)]
+    pub lighting: LevelLighting,
+    /// Asset path of the theme of the level, the theme of the pack when not set.
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Colors of snakes by index, player snakes first then npc snakes. The other snakes use the
+    /// palette of the theme.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub snake_colors: BTreeMap<usize, Color>,
+    /// The line of each entity in the file the level was read from, to report errors.
+    #[serde(skip)]
+    pub entity_lines: Vec<usize>,
+}
+
+impl Default for LevelTemplate {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_LEVEL_VERSION,
+            snakes: Default::default(),
+            entities: Default::default(),
+            gravity: default_gravity(),
+            npc_snakes: Default::default(),
+            par: None,
+            planar: false,
+            metadata: Default::default(),
+            decorations: Default::default(),
+            lighting: Default::default(),
+            theme: None,
+            snake_colors: Default::default(),
+            entity_lines: Default::default(),
+        }
+    }
+}
+
+impl LevelTemplate {
+    /// Give an id to the entities that don't have one, after the highest id of the level.
+    pub fn assign_missing_ids(&mut self) {
+        let mut next_id = self
+            .entities
+            .iter()
+            .map(|entity| entity.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        for entity in self.entities.iter_mut().filter(|entity| entity.id == 0) {
+            entity.id = next_id;
+            next_id += 1;
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -78,8 +259,41 @@ pub struct LoadingLevel(pub Handle<LevelTemplate>);
 #[derive(Resource)]
 pub struct LoadedLevel(pub Handle<LevelTemplate>);
 
+/// The file of a level asset on disk, levels opened from elsewhere have an absolute asset path.
+pub fn level_file_path(asset_path: &str) -> PathBuf {
+    Path::new("assets").join(asset_path)
+}
+
+/// Read a level file of any version and format, `file` is used to pick the format and report
+/// errors.
+pub fn parse_level(bytes: &[u8], file: &str) -> Result<LevelTemplate, LevelLoadError> {
+    let template = if file.ends_with(ASCII_LEVEL_EXTENSION) {
+        let text = std::str::from_utf8(bytes).map_err(|error| {
+            LevelLoadError::new(file, LevelLoadErrorKind::Parse(error.to_string()))
+        })?;
+
+        let mut template = level_from_ascii(text, file)?;
+        migrate(&mut template, file)?;
+        template
+    } else {
+        let mut template = load_level_template(bytes, file)?;
+
+        // Files in the oldest layout have no entities list to take the lines from.
+        let lines = ron_list_item_lines(&String::from_utf8_lossy(bytes), "entities");
+        if lines.len() == template.entities.len() {
+            template.entity_lines = lines;
+        }
+        template
+    };
+
+    validate_template(&template, file)?;
+    Ok(template)
+}
+
 #[derive(Default)]
-pub struct LevelTemplateLoader;
+pub struct LevelTemplateLoader {
+    pub errors: LevelLoadErrors,
+}
 
 impl AssetLoader for LevelTemplateLoader {
     fn load<'a>(
@@ -88,13 +302,19 @@ impl AssetLoader for LevelTemplateLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
         Box::pin(async move {
-            let custom_asset = ron::de::from_bytes::<LevelTemplate>(bytes)?;
+            let file = load_context.path().to_string_lossy().into_owned();
+
+            let custom_asset = parse_level(bytes, &file).map_err(|error| {
+                self.errors.insert(error.clone());
+                error
+            })?;
+
             load_context.set_default_asset(LoadedAsset::new(custom_asset));
             Ok(())
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["lvl"]
+        &["lvl", ASCII_LEVEL_EXTENSION]
     }
 }
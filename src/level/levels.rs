@@ -1,8 +0,0 @@
-pub const LEVELS: [&str; 6] = [
-    "new.lvl",
-    "level1.lvl",
-    "level2.lvl",
-    "level3.lvl",
-    "level4.lvl",
-    "level5.lvl",
-];
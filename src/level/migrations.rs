@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use ron::{extensions::Extensions, Options};
+use serde::Deserialize;
+
+use crate::gameplay::{
+    game_constants_plugin::BACKGROUND_COLOR, level_entities::EntityType,
+    snake_plugin::SnakeTemplate,
+};
+
+use super::{
+    level_load_error::{LevelLoadError, LevelLoadErrorKind},
+    level_template::{parse_level, DefaultModel, EntityTemplate, LevelTemplate, Model},
+};
+
+/// The version written in new level files.
+pub const CURRENT_LEVEL_VERSION: u32 = 3;
+
+/// The first version where the background color of the lighting can be left out, older files
+/// are read with implicit `Some`.
+const OPTIONAL_BACKGROUND_VERSION: u32 = 3;
+
+/// Upgrade a level from the version at the index plus one to the next one.
+/// Add a function here and bump `CURRENT_LEVEL_VERSION` when the schema changes.
+const MIGRATIONS: &[fn(&mut LevelTemplate)] = &[add_entity_ids, use_theme_background];
+
+/// Version 2 gives every entity an id.
+fn add_entity_ids(template: &mut LevelTemplate) {
+    template.assign_missing_ids();
+}
+
+/// Version 3 lets the theme choose the background. Version 2 files always wrote the background,
+/// the default one now follows the theme.
+fn use_theme_background(template: &mut LevelTemplate) {
+    let background = &mut template.lighting.background_color;
+    if *background == Some(BACKGROUND_COLOR) {
+        *background = None;
+    }
+}
+
+/// Files saved before the version field existed have the layout of the first version.
+pub fn unversioned() -> u32 {
+    1
+}
+
+/// Only reads the version of a level file.
+#[derive(Deserialize)]
+struct LevelVersion {
+    #[serde(default = "unversioned")]
+    version: u32,
+}
+
+/// The layout before entities were stored in a single list, as read by the old converter.
+#[derive(Deserialize)]
+struct LevelTemplateV0 {
+    snakes: Vec<SnakeTemplate>,
+    walls: Vec<IVec3>,
+    #[serde(default)]
+    foods: Vec<IVec3>,
+    #[serde(default)]
+    spikes: Vec<IVec3>,
+    #[serde(default)]
+    boxes: Vec<IVec3>,
+    #[serde(default)]
+    triggers: Vec<IVec3>,
+    #[serde(default)]
+    goal: Option<IVec3>,
+}
+
+impl From<LevelTemplateV0> for LevelTemplate {
+    fn from(value: LevelTemplateV0) -> Self {
+        let entities = [
+            (EntityType::Wall, DefaultModel::Wall, value.walls),
+            (EntityType::Food, DefaultModel::Food, value.foods),
+            (EntityType::Spike, DefaultModel::Spike, value.spikes),
+            (EntityType::Box, DefaultModel::Box, value.boxes),
+            (EntityType::Trigger, DefaultModel::Trigger, value.triggers),
+            (
+                EntityType::Goal,
+                DefaultModel::Goal,
+                value.goal.into_iter().collect(),
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(entity_type, model, positions)| {
+            positions.into_iter().map(move |position| EntityTemplate {
+                entity_type,
+                model: Model::Default(model),
+                grid_position: position,
+                ..default()
+            })
+        })
+        .collect();
+
+        LevelTemplate {
+            version: 1,
+            snakes: value.snakes,
+            entities,
+            ..default()
+        }
+    }
+}
+
+/// Parse a level file of any version and upgrade it to the current one.
+pub fn load_level_template(bytes: &[u8], file: &str) -> Result<LevelTemplate, LevelLoadError> {
+    let LevelVersion { version } =
+        ron::de::from_bytes(bytes).map_err(|error| LevelLoadError::parse(file, &error))?;
+
+    let options = if version < OPTIONAL_BACKGROUND_VERSION {
+        Options::default().with_default_extension(Extensions::IMPLICIT_SOME)
+    } else {
+        Options::default()
+    };
+
+    let mut template = match options.from_bytes::<LevelTemplate>(bytes) {
+        Ok(template) => template,
+        // The oldest files are unversioned too, but have a different layout.
+        Err(error) if version == unversioned() => ron::de::from_bytes::<LevelTemplateV0>(bytes)
+            .map(LevelTemplate::from)
+            .map_err(|_| LevelLoadError::parse(file, &error))?,
+        Err(error) => return Err(LevelLoadError::parse(file, &error)),
+    };
+
+    migrate(&mut template, file)?;
+    Ok(template)
+}
+
+/// Run the migrations following the version of the template, fails for files saved by a newer
+/// version of the game.
+pub fn migrate(template: &mut LevelTemplate, file: &str) -> Result<(), LevelLoadError> {
+    if template.version > CURRENT_LEVEL_VERSION {
+        return Err(LevelLoadError::new(
+            file,
+            LevelLoadErrorKind::UnsupportedVersion(template.version),
+        ));
+    }
+
+    let applied = template.version.saturating_sub(1) as usize;
+    for migration in MIGRATIONS.iter().skip(applied) {
+        migration(template);
+    }
+
+    template.version = CURRENT_LEVEL_VERSION;
+    Ok(())
+}
+
+/// The levels of a folder rewritten by `migrate_levels_in`.
+#[derive(Debug, Default)]
+pub struct MigratedLevels {
+    pub upgraded: usize,
+    /// Levels that could not be read, they are left untouched.
+    pub skipped: usize,
+}
+
+/// Rewrite every level under the folder with the current version. Text maps are left alone,
+/// they are laid out by hand and rewriting them would lose it, they are upgraded when loaded.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn migrate_levels_in(folder: &std::path::Path) -> std::io::Result<MigratedLevels> {
+    let mut migrated = MigratedLevels::default();
+
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            let folder = migrate_levels_in(&path)?;
+            migrated.upgraded += folder.upgraded;
+            migrated.skipped += folder.skipped;
+            continue;
+        }
+
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "lvl")
+        {
+            continue;
+        }
+
+        let file = path.display().to_string();
+        let bytes = std::fs::read(&path)?;
+        let LevelVersion { version } = match ron::de::from_bytes(&bytes) {
+            Ok(version) => version,
+            Err(error) => {
+                eprintln!("Skipping {}", LevelLoadError::parse(&file, &error));
+                migrated.skipped += 1;
+                continue;
+            }
+        };
+
+        if version == CURRENT_LEVEL_VERSION {
+            continue;
+        }
+
+        let template = match parse_level(&bytes, &file) {
+            Ok(template) => template,
+            Err(error) => {
+                eprintln!("Skipping {}", error);
+                migrated.skipped += 1;
+                continue;
+            }
+        };
+
+        let ron_string =
+            ron::ser::to_string_pretty(&template, ron::ser::PrettyConfig::default()).unwrap();
+        std::fs::write(&path, ron_string)?;
+
+        println!("Migrated {} to version {}", file, template.version);
+        migrated.upgraded += 1;
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_files_are_migrated() {
+        let level = b"(snakes: [], entities: [(entity_type: Wall, model: Default(Wall), \
+                      grid_position: (0, 0, 0))])";
+        let template = load_level_template(level, "test.lvl").unwrap();
+        assert_eq!(template.version, CURRENT_LEVEL_VERSION);
+        assert_eq!(template.entities[0].id, 1);
+    }
+
+    #[test]
+    fn version_two_backgrounds_are_read_as_set() {
+        let level = b"(version: 2, snakes: [], entities: [], lighting: (background_color: \
+                      Rgba(red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0)))";
+        let template = load_level_template(level, "test.lvl").unwrap();
+        assert_eq!(template.lighting.background_color, Some(Color::RED));
+
+        let level = format!(
+            "(version: 2, snakes: [], entities: [], lighting: (background_color: {}))",
+            ron::to_string(&BACKGROUND_COLOR).unwrap()
+        );
+        let template = load_level_template(level.as_bytes(), "test.lvl").unwrap();
+        assert_eq!(template.lighting.background_color, None);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let level = format!(
+            "(version: {}, snakes: [], entities: [])",
+            CURRENT_LEVEL_VERSION + 1
+        );
+        let error = load_level_template(level.as_bytes(), "test.lvl").unwrap_err();
+        assert_eq!(
+            error.kind,
+            LevelLoadErrorKind::UnsupportedVersion(CURRENT_LEVEL_VERSION + 1)
+        );
+    }
+}
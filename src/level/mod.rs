@@ -1,3 +1,10 @@
+pub mod ascii_level;
 pub mod level_instance;
+pub mod level_load_error;
+pub mod level_pack;
 pub mod level_template;
-pub mod levels;
+pub mod migrations;
+pub mod model_catalog;
+pub mod snakebird_import;
+pub mod svg_preview;
+pub mod vox;
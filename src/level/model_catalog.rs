@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{gameplay::level_entities::EntityType, library::AssetLibrary};
+
+pub const MODEL_CATALOG_EXTENSION: &str = "catalog";
+
+fn single_cell() -> Vec<IVec3> {
+    vec![IVec3::ZERO]
+}
+
+/// Entities that move or get removed, the level only follows the cell at their position.
+pub fn needs_single_cell(entity_type: EntityType) -> bool {
+    matches!(
+        entity_type,
+        EntityType::Box | EntityType::Food | EntityType::BreakableWall
+    )
+}
+
+/// How a model of the models folder is used in levels.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CatalogModel {
+    /// Shown in the editor palette.
+    pub name: String,
+    /// The entities that can be drawn with the model.
+    pub entity_types: Vec<EntityType>,
+    /// The cells covered by the model, relative to the grid position of the entity and before
+    /// its rotation.
+    #[serde(default = "single_cell")]
+    pub footprint: Vec<IVec3>,
+    /// Added to the grid position to place the model, in the model space.
+    #[serde(default)]
+    pub pivot: Vec3,
+    /// The rotation of the model when it is placed in the editor.
+    #[serde(default)]
+    pub rotation: Quat,
+}
+
+impl CatalogModel {
+    /// Models covering more than one cell can't draw the entities that need a single one.
+    pub fn allows(&self, entity_type: EntityType) -> bool {
+        self.entity_types.contains(&entity_type)
+            && (self.footprint == single_cell() || !needs_single_cell(entity_type))
+    }
+
+    /// Where the model is drawn for an entity at `position`.
+    pub fn transform(&self, position: IVec3, rotation: Quat) -> Transform {
+        Transform::from_translation(position.as_vec3() + rotation * self.pivot)
+            .with_rotation(rotation)
+    }
+}
+
+/// The models of the models folder that can be placed in levels, keyed by asset path.
+/// Models missing from the catalog cover one cell and can be used for any entity.
+#[derive(Deserialize, Serialize, TypeUuid, Debug, Clone, Default)]
+#[uuid = "5e1d7a2c-8b4f-4c36-9d0e-2a7f6c3b1e95"]
+pub struct ModelCatalog {
+    pub models: BTreeMap<String, CatalogModel>,
+}
+
+impl ModelCatalog {
+    pub fn get(&self, path: &str) -> Option<&CatalogModel> {
+        self.models.get(path)
+    }
+
+    /// The models that can draw the entity type, in the order of the palette.
+    pub fn models_for(
+        &self,
+        entity_type: EntityType,
+    ) -> impl Iterator<Item = (&String, &CatalogModel)> {
+        self.models
+            .iter()
+            .filter(move |(_, model)| model.allows(entity_type))
+    }
+
+    pub fn allows(&self, path: &str, entity_type: EntityType) -> bool {
+        self.get(path)
+            .map_or(true, |model| model.allows(entity_type))
+    }
+
+    pub fn footprint(&self, path: &str) -> Footprint {
+        Footprint(
+            self.get(path)
+                .map_or_else(single_cell, |model| model.footprint.clone()),
+        )
+    }
+
+    /// The catalog of the library, empty until it is loaded.
+    pub fn of_library<'a>(library: &AssetLibrary, catalogs: &'a Assets<ModelCatalog>) -> &'a Self {
+        static EMPTY: ModelCatalog = ModelCatalog {
+            models: BTreeMap::new(),
+        };
+
+        catalogs.get(&library.catalog).unwrap_or(&EMPTY)
+    }
+}
+
+/// The cells covered by a grid entity drawn with a multi-cell model, relative to its position
+/// and before its rotation. Entities without it cover their position only.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Footprint(pub Vec<IVec3>);
+
+impl Footprint {
+    pub fn is_single_cell(&self) -> bool {
+        self.0 == single_cell()
+    }
+
+    /// The cells covered by the footprint of an entity at `position`, rotated with the entity.
+    pub fn cells(&self, position: IVec3, rotation: Quat) -> Vec<IVec3> {
+        self.0
+            .iter()
+            .map(|offset| position + (rotation * offset.as_vec3()).round().as_ivec3())
+            .collect()
+    }
+}
+
+/// Where the model of a grid entity is drawn from its position, in the model space. Entities
+/// without it are drawn at their position.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPivot(pub Vec3);
+
+/// The cells covered by a grid entity, with or without a footprint.
+pub fn occupied_cells(
+    position: IVec3,
+    footprint: Option<&Footprint>,
+    rotation: Quat,
+) -> Vec<IVec3> {
+    match footprint {
+        Some(footprint) => footprint.cells(position, rotation),
+        None => vec![position],
+    }
+}
+
+#[derive(Default)]
+pub struct ModelCatalogLoader;
+
+impl AssetLoader for ModelCatalogLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let catalog = ron::de::from_bytes::<ModelCatalog>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(catalog));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[MODEL_CATALOG_EXTENSION]
+    }
+}
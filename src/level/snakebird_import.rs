@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::gameplay::{level_entities::EntityType, snake_plugin::SnakeTemplate};
+
+use super::{
+    level_load_error::{LevelLoadError, LevelLoadErrorKind},
+    level_template::{DefaultModel, EntityTemplate, LevelTemplate, Model},
+};
+
+/// The z of the plane the imported levels are built in.
+pub const PLANAR_DEPTH: i32 = 0;
+
+const PLANAR_NEIGHBOURS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y];
+
+/// The entity drawn by a character of a 2D puzzle, `None` for the empty cells and snakes.
+fn entity_for_character(character: char) -> Option<(EntityType, DefaultModel)> {
+    match character {
+        '#' => Some((EntityType::Wall, DefaultModel::Wall)),
+        '%' => Some((EntityType::BreakableWall, DefaultModel::BreakableWall)),
+        '^' => Some((EntityType::Spike, DefaultModel::Spike)),
+        '*' => Some((EntityType::Food, DefaultModel::Food)),
+        '@' => Some((EntityType::Goal, DefaultModel::Goal)),
+        '=' => Some((EntityType::Box, DefaultModel::Box)),
+        _ => None,
+    }
+}
+
+/// Build a level from a side view 2D puzzle, as found in Snakebird like games.
+///
+/// The first line is the top of the level. `#` are walls, `%` breakable walls, `^` spikes,
+/// `*` fruits, `@` the exit and `=` boxes, `.` and spaces are empty. Each snake is drawn with an
+/// uppercase letter for its head and the lowercase letter for its body, which must not touch
+/// itself so it can be followed from the head.
+/// The level is built in the x/y plane at `PLANAR_DEPTH` and only allows planar movement.
+pub fn import_snakebird(text: &str, file: &str) -> Result<LevelTemplate, LevelLoadError> {
+    // Blank lines around the puzzle are dropped, the ones inside are empty rows.
+    let mut rows: Vec<&str> = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    while rows.last().map_or(false, |line| line.trim().is_empty()) {
+        rows.pop();
+    }
+    let height = rows.len() as i32;
+
+    let mut entities = vec![];
+    let mut heads: Vec<(char, IVec3)> = vec![];
+    let mut bodies: HashMap<IVec3, char> = HashMap::new();
+
+    for (row, line) in rows.iter().enumerate() {
+        let y = height - 1 - row as i32;
+
+        for (column, character) in line.chars().enumerate() {
+            let position = IVec3::new(column as i32, y, PLANAR_DEPTH);
+
+            if character == '.' || character == ' ' {
+                continue;
+            }
+
+            if character.is_ascii_uppercase() {
+                if heads.iter().any(|(head, _)| *head == character) {
+                    return Err(import_error(
+                        file,
+                        format!("snake {} has two heads", character),
+                    ));
+                }
+                heads.push((character, position));
+            } else if character.is_ascii_lowercase() {
+                bodies.insert(position, character);
+            } else if let Some((entity_type, model)) = entity_for_character(character) {
+                entities.push(EntityTemplate {
+                    entity_type,
+                    model: Model::Default(model),
+                    grid_position: position,
+                    ..default()
+                });
+            } else {
+                return Err(import_error(
+                    file,
+                    format!("unknown character '{}'", character),
+                ));
+            }
+        }
+    }
+
+    // Snakes are ordered by letter, the first one is selected when the level starts.
+    heads.sort_by_key(|(head, _)| *head);
+
+    let snakes = heads
+        .into_iter()
+        .map(|(head, position)| trace_planar_snake(&mut bodies, head, position, file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(character) = bodies.values().next() {
+        return Err(import_error(
+            file,
+            format!("snake body '{}' has no head", character),
+        ));
+    }
+
+    let mut template = LevelTemplate {
+        snakes,
+        entities,
+        planar: true,
+        ..default()
+    };
+    template.assign_missing_ids();
+    Ok(template)
+}
+
+fn import_error(file: &str, message: String) -> LevelLoadError {
+    LevelLoadError::new(file, LevelLoadErrorKind::Parse(message))
+}
+
+/// Follow the body of a snake from its head, removing the parts from `bodies`.
+fn trace_planar_snake(
+    bodies: &mut HashMap<IVec3, char>,
+    head: char,
+    head_position: IVec3,
+    file: &str,
+) -> Result<SnakeTemplate, LevelLoadError> {
+    let body = head.to_ascii_lowercase();
+    let mut positions = vec![head_position];
+
+    loop {
+        let last = *positions.last().unwrap();
+        let mut next_parts = PLANAR_NEIGHBOURS
+            .iter()
+            .map(|offset| last + *offset)
+            .filter(|position| bodies.get(position) == Some(&body));
+
+        let Some(next) = next_parts.next() else {
+            break;
+        };
+
+        if next_parts.next().is_some() {
+            return Err(import_error(
+                file,
+                format!("the body of snake {} can't be followed", head),
+            ));
+        }
+
+        bodies.remove(&next);
+        positions.push(next);
+    }
+
+    if positions.len() < 2 {
+        return Err(import_error(file, format!("snake {} has no body", head)));
+    }
+
+    // A part points away from the part behind it, the tail points like the part before it.
+    let directions = positions
+        .windows(2)
+        .map(|parts| parts[0] - parts[1])
+        .chain(std::iter::once(
+            positions[positions.len() - 2] - positions[positions.len() - 1],
+        ));
+
+    Ok(positions.iter().copied().zip(directions).collect())
+}
+
+/// Import the puzzle at `source` and write it as a level file to `destination`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn import_snakebird_file(
+    source: &std::path::Path,
+    destination: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(source)?;
+    let template = import_snakebird(&text, &source.display().to_string())?;
+
+    let ron_string = ron::ser::to_string_pretty(&template, ron::ser::PrettyConfig::default())?;
+    std::fs::write(destination, ron_string)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_rows_inside_the_puzzle_are_kept() {
+        let template = import_snakebird("\n###\n\n###\n\n", "puzzle.txt").unwrap();
+
+        let mut heights: Vec<i32> = template
+            .entities
+            .iter()
+            .map(|entity| entity.grid_position.y)
+            .collect();
+        heights.dedup();
+
+        assert_eq!(heights, vec![2, 0]);
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::gameplay::{
+    level_entities::EntityType,
+    theme_plugin::{snake_shades, snake_stripe, Theme},
+};
+
+use super::level_template::LevelTemplate;
+
+pub const SVG_EXTENSION: &str = "svg";
+
+/// The size in pixels of the edge of a grid cell.
+const CELL_SIZE: f32 = 32.0;
+const MARGIN: f32 = 16.0;
+
+/// The brightness of the top, right and left faces of a block.
+const FACE_SHADES: [f32; 3] = [1.0, 0.8, 0.6];
+
+const SOLUTION_COLOR: &str = "#d02060";
+
+/// An axis aligned box drawn in the preview, in grid units.
+struct Block {
+    center: Vec3,
+    half_size: Vec3,
+    color: Color,
+}
+
+impl Block {
+    /// Blocks further from the viewer are drawn first.
+    fn depth(&self) -> f32 {
+        self.center.x + self.center.y + self.center.z
+    }
+}
+
+/// The shape and color of the entities of the level.
+fn entity_block(entity_type: EntityType, position: IVec3, theme: &Theme) -> Option<Block> {
+    let center = position.as_vec3();
+    let (half_size, color) = match entity_type {
+        EntityType::Wall => (Vec3::splat(0.5), theme.wall_tint),
+        EntityType::BreakableWall => (Vec3::splat(0.5), theme.breakable_wall),
+        EntityType::Box => (Vec3::splat(0.45), theme.box_color),
+        EntityType::Spike => (Vec3::new(0.3, 0.5, 0.3), theme.spike),
+        EntityType::Food => (Vec3::splat(0.25), theme.food),
+        EntityType::Goal => (Vec3::splat(0.35), theme.goal),
+        EntityType::GravitySwitch => (Vec3::splat(0.3), theme.gravity_switch),
+        EntityType::Trigger => {
+            // A flat plate on the floor of the cell.
+            return Some(Block {
+                center: center - Vec3::Y * 0.45,
+                half_size: Vec3::new(0.4, 0.05, 0.4),
+                color: theme.trigger,
+            });
+        }
+        EntityType::Snake => return None,
+    };
+
+    Some(Block {
+        center,
+        half_size,
+        color,
+    })
+}
+
+/// Isometric projection seen from +x, +y, +z, in pixels.
+fn project(point: Vec3) -> Vec2 {
+    let cos = 30f32.to_radians().cos();
+    Vec2::new(
+        (point.x - point.z) * cos * CELL_SIZE,
+        ((point.x + point.z) * 0.5 - point.y) * CELL_SIZE,
+    )
+}
+
+fn svg_color(color: Color, shade: f32) -> String {
+    let [r, g, b, _] = color.as_rgba_f32();
+    let channel = |value: f32| (value * shade * 255.0).round().clamp(0.0, 255.0) as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+fn points(corners: &[Vec3], offset: Vec2) -> String {
+    corners
+        .iter()
+        .map(|corner| {
+            let point = project(*corner) + offset;
+            format!("{:.1},{:.1}", point.x, point.y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The top, +x and +z faces of the block, the only ones facing the viewer.
+fn block_faces(block: &Block) -> [[Vec3; 4]; 3] {
+    let min = block.center - block.half_size;
+    let max = block.center + block.half_size;
+
+    [
+        [
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(max.x, max.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+        ],
+        [
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(max.x, max.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(max.x, min.y, min.z),
+        ],
+        [
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, min.y, max.z),
+        ],
+    ]
+}
+
+/// Read the cells of a solution path, written as `x,y,z` and separated by spaces or `;`.
+/// The path is drawn as written, the movement rules are not played.
+pub fn parse_cells(text: &str) -> Result<Vec<IVec3>, String> {
+    text.split(|character: char| character.is_whitespace() || character == ';')
+        .filter(|cell| !cell.is_empty())
+        .map(|cell| {
+            let coordinates = cell
+                .split(',')
+                .map(|coordinate| coordinate.parse::<i32>())
+                .collect::<Result<Vec<_>, _>>();
+            match coordinates.as_deref() {
+                Ok(&[x, y, z]) => Ok(IVec3::new(x, y, z)),
+                _ => Err(format!("'{}' is not a cell, expected x,y,z", cell)),
+            }
+        })
+        .collect()
+}
+
+/// Draw the level as an isometric SVG image, without needing a GPU.
+/// The solution is the list of cells visited by a snake head, drawn as a line over the level.
+pub fn level_to_svg(template: &LevelTemplate, theme: &Theme, solution: Option<&[IVec3]>) -> String {
+    let mut blocks: Vec<Block> = template
+        .entities
+        .iter()
+        .filter_map(|entity| entity_block(entity.entity_type, entity.grid_position, theme))
+        .collect();
+
+    let snakes = template
+        .snakes
+        .iter()
+        .chain(template.npc_snakes.iter().map(|npc| &npc.parts));
+
+    for (snake_index, snake) in snakes.enumerate() {
+        let colors = match template.snake_colors.get(&snake_index) {
+            Some(color) => snake_shades(*color),
+            None => theme.snake_colors(snake_index, false),
+        };
+        for (part_index, (position, _)) in snake.iter().enumerate() {
+            blocks.push(Block {
+                center: position.as_vec3(),
+                half_size: Vec3::splat(0.45),
+                color: colors[snake_stripe(snake_index, part_index)],
+            });
+        }
+    }
+
+    blocks.sort_by(|a, b| a.depth().total_cmp(&b.depth()));
+
+    let path: Vec<Vec3> = solution
+        .unwrap_or_default()
+        .iter()
+        .map(|cell| cell.as_vec3())
+        .collect();
+
+    // Fit the image to the projected corners of everything drawn.
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    let corners = blocks
+        .iter()
+        .flat_map(block_faces)
+        .flatten()
+        .chain(path.iter().copied());
+    for corner in corners {
+        let point = project(corner);
+        min = min.min(point);
+        max = max.max(point);
+    }
+    if min.x > max.x {
+        min = Vec2::ZERO;
+        max = Vec2::ZERO;
+    }
+
+    let offset = Vec2::splat(MARGIN) - min;
+    let size = max - min + Vec2::splat(2.0 * MARGIN);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0:.0}\" height=\"{1:.0}\" \
+         viewBox=\"0 0 {0:.0} {1:.0}\">",
+        size.x, size.y
+    )
+    .unwrap();
+
+    for block in &blocks {
+        for (face, shade) in block_faces(block).iter().zip(FACE_SHADES) {
+            writeln!(
+                svg,
+                r##"  <polygon points="{}" fill="{}" stroke="#00000040" stroke-width="0.5"/>"##,
+                points(face, offset),
+                svg_color(block.color, shade)
+            )
+            .unwrap();
+        }
+    }
+
+    if let (Some(start), Some(end)) = (path.first(), path.last()) {
+        writeln!(
+            svg,
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"3\" \
+             stroke-linejoin=\"round\"/>",
+            points(&path, offset),
+            SOLUTION_COLOR
+        )
+        .unwrap();
+
+        for (cell, radius) in [(start, 5.0), (end, 3.0)] {
+            let point = project(*cell) + offset;
+            writeln!(
+                svg,
+                r#"  <circle cx="{:.1}" cy="{:.1}" r="{}" fill="{}"/>"#,
+                point.x, point.y, radius, SOLUTION_COLOR
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Write an SVG preview of the level file at `source` to `destination` with the default theme,
+/// with the cells of the solution path when given.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_svg_file(
+    source: &std::path::Path,
+    destination: &std::path::Path,
+    solution: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(source)?;
+    let template = super::level_template::parse_level(&bytes, &source.display().to_string())?;
+    let path = solution.map(parse_cells).transpose()?;
+
+    let svg = level_to_svg(&template, &Theme::default(), path.as_deref());
+    std::fs::write(destination, svg)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::level_template::EntityTemplate;
+
+    #[test]
+    fn cells_are_read_as_coordinates() {
+        assert_eq!(
+            parse_cells("0,1,0 1,1,0;\n1,2,-3"),
+            Ok(vec![
+                IVec3::new(0, 1, 0),
+                IVec3::new(1, 1, 0),
+                IVec3::new(1, 2, -3)
+            ])
+        );
+        assert!(parse_cells("0,1,0 1,1").is_err());
+        assert!(parse_cells("0,1,x").is_err());
+    }
+
+    #[test]
+    fn every_block_draws_its_visible_faces() {
+        let template = LevelTemplate {
+            snakes: vec![vec![
+                (IVec3::new(0, 1, 0), IVec3::X),
+                (IVec3::ZERO, IVec3::Y),
+            ]],
+            entities: [IVec3::new(1, 0, 0), IVec3::new(2, 0, 0)]
+                .into_iter()
+                .map(|grid_position| EntityTemplate {
+                    grid_position,
+                    ..default()
+                })
+                .collect(),
+            ..default()
+        };
+
+        let path = parse_cells("0,1,0 1,1,0 2,1,0").unwrap();
+        let svg = level_to_svg(&template, &Theme::default(), Some(&path));
+
+        // Two walls and two snake parts, with three faces each.
+        assert_eq!(svg.matches("<polygon").count(), 4 * 3);
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 2);
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::level_entities::EntityType;
+
+use super::{
+    level_load_error::{LevelLoadError, LevelLoadErrorKind},
+    level_template::{level_file_path, DefaultModel, EntityTemplate, LevelTemplate, Model},
+};
+
+pub const VOX_EXTENSION: &str = "vox";
+
+const VOX_VERSION: i32 = 150;
+
+/// The file of the palette next to the level folders, read when importing and exporting.
+pub const VOX_PALETTE_PATH: &str = "vox.palette";
+
+/// What the voxels of a palette index stand for.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct VoxPaletteEntry {
+    pub entity_type: EntityType,
+    pub model: Model,
+    /// The color written in exported files.
+    pub color: [u8; 4],
+}
+
+/// The entity of each palette index of MagicaVoxel files. Index 0 is empty in MagicaVoxel,
+/// voxels of the indices missing here are skipped.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct VoxPalette {
+    pub entries: BTreeMap<u8, VoxPaletteEntry>,
+}
+
+impl Default for VoxPalette {
+    fn default() -> Self {
+        let entries = [
+            (EntityType::Wall, DefaultModel::Wall, [200, 180, 150, 255]),
+            (
+                EntityType::BreakableWall,
+                DefaultModel::BreakableWall,
+                [150, 110, 80, 255],
+            ),
+            (EntityType::Spike, DefaultModel::Spike, [200, 40, 40, 255]),
+            (EntityType::Box, DefaultModel::Box, [120, 120, 120, 255]),
+            (EntityType::Food, DefaultModel::Food, [250, 160, 30, 255]),
+            (EntityType::Goal, DefaultModel::Goal, [250, 230, 60, 255]),
+            (
+                EntityType::Trigger,
+                DefaultModel::Trigger,
+                [60, 160, 220, 255],
+            ),
+            (
+                EntityType::GravitySwitch,
+                DefaultModel::GravitySwitch,
+                [170, 80, 220, 255],
+            ),
+        ];
+
+        Self {
+            entries: (1..)
+                .zip(entries)
+                .map(|(index, (entity_type, model, color))| {
+                    let entry = VoxPaletteEntry {
+                        entity_type,
+                        model: Model::Default(model),
+                        color,
+                    };
+                    (index, entry)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl VoxPalette {
+    /// Read a palette file, `None` reads the one of the assets folder. The default palette is
+    /// used when the assets have none.
+    pub fn read(path: Option<&Path>) -> Result<Self, LevelLoadError> {
+        let path = path.map_or_else(|| level_file_path(VOX_PALETTE_PATH), Path::to_path_buf);
+        let file = path.display().to_string();
+
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                ron::de::from_bytes(&bytes).map_err(|error| LevelLoadError::parse(&file, &error))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(default()),
+            Err(error) => Err(vox_error(&file, &error.to_string())),
+        }
+    }
+
+    /// The index of the entry with the model of the entity, else the first one of its type.
+    fn index_of(&self, entity: &EntityTemplate) -> Option<u8> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| {
+                entry.entity_type == entity.entity_type && entry.model == entity.model
+            })
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .find(|(_, entry)| entry.entity_type == entity.entity_type)
+            })
+            .map(|(index, _)| *index)
+    }
+}
+
+fn vox_error(file: &str, message: &str) -> LevelLoadError {
+    LevelLoadError::new(file, LevelLoadErrorKind::Parse(message.to_owned()))
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(i32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Read the entities of a MagicaVoxel file, the voxels of every model are used without their
+/// transforms. MagicaVoxel is z up, so the voxel z is the level y.
+pub fn entities_from_vox(
+    bytes: &[u8],
+    file: &str,
+    palette: &VoxPalette,
+) -> Result<Vec<EntityTemplate>, LevelLoadError> {
+    if bytes.get(0..4) != Some(b"VOX ".as_slice()) {
+        return Err(vox_error(file, "not a MagicaVoxel file"));
+    }
+
+    let mut entities = vec![];
+    let mut skipped_indices = BTreeSet::new();
+    let mut size_y = 0;
+
+    // Skip the header and the MAIN chunk header, its children follow.
+    let mut offset = 20;
+    while offset + 12 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let content_size = read_i32(bytes, offset + 4).unwrap_or_default().max(0) as usize;
+        let content = bytes
+            .get(offset + 12..offset + 12 + content_size)
+            .ok_or_else(|| vox_error(file, "truncated chunk"))?;
+
+        match id {
+            b"SIZE" => {
+                size_y = read_i32(content, 4).ok_or_else(|| vox_error(file, "invalid size"))?;
+            }
+            b"XYZI" => {
+                let count = read_i32(content, 0).unwrap_or_default().max(0) as usize;
+                let voxels = content
+                    .get(4..4 + 4 * count)
+                    .ok_or_else(|| vox_error(file, "truncated voxels"))?;
+
+                for voxel in voxels.chunks_exact(4) {
+                    let Some(entry) = palette.entries.get(&voxel[3]) else {
+                        skipped_indices.insert(voxel[3]);
+                        continue;
+                    };
+
+                    let (x, y, z) = (voxel[0] as i32, voxel[1] as i32, voxel[2] as i32);
+                    entities.push(EntityTemplate {
+                        entity_type: entry.entity_type,
+                        model: entry.model.clone(),
+                        grid_position: IVec3::new(x, z, size_y - 1 - y),
+                        ..default()
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        // Children of the other chunks are walked through like siblings.
+        offset += 12 + content_size;
+    }
+
+    if !skipped_indices.is_empty() {
+        warn!(
+            "{}: skipped the voxels of palette indices {:?}, they are not in the palette",
+            file, skipped_indices
+        );
+    }
+
+    Ok(entities)
+}
+
+/// A level holding only the geometry of a MagicaVoxel file, snakes are added in the editor.
+pub fn level_from_vox(
+    bytes: &[u8],
+    file: &str,
+    palette: &VoxPalette,
+) -> Result<LevelTemplate, LevelLoadError> {
+    let mut template = LevelTemplate {
+        entities: entities_from_vox(bytes, file, palette)?,
+        ..default()
+    };
+    template.assign_missing_ids();
+    Ok(template)
+}
+
+fn write_chunk(output: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    output.extend_from_slice(id);
+    output.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    output.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    output.extend_from_slice(content);
+    output.extend_from_slice(children);
+}
+
+/// Write the entities of the level as a MagicaVoxel file. Snakes and rotations are not kept,
+/// entities use the palette index of their model, else of their type.
+pub fn level_to_vox(template: &LevelTemplate, palette: &VoxPalette) -> io::Result<Vec<u8>> {
+    let voxels: Vec<(IVec3, u8)> = template
+        .entities
+        .iter()
+        .filter_map(|entity| Some((entity.grid_position, palette.index_of(entity)?)))
+        .collect();
+
+    let min = voxels
+        .iter()
+        .fold(IVec3::splat(i32::MAX), |min, (cell, _)| min.min(*cell));
+    let max = voxels
+        .iter()
+        .fold(IVec3::splat(i32::MIN), |max, (cell, _)| max.max(*cell));
+    let size = if voxels.is_empty() {
+        IVec3::ONE
+    } else {
+        max - min + IVec3::ONE
+    };
+
+    if size.max_element() > 256 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the level is larger than 256 voxels",
+        ));
+    }
+
+    // Level y is the voxel z, and z is flipped to keep the handedness.
+    let mut size_content = vec![];
+    for dimension in [size.x, size.z, size.y] {
+        size_content.extend_from_slice(&dimension.to_le_bytes());
+    }
+
+    let mut voxels_content = (voxels.len() as i32).to_le_bytes().to_vec();
+    for (position, index) in &voxels {
+        let offset = *position - min;
+        let y = size.z - 1 - offset.z;
+        voxels_content.extend_from_slice(&[offset.x as u8, y as u8, offset.y as u8, *index]);
+    }
+
+    // The palette chunk starts with the color of index 1.
+    let mut palette_content = vec![];
+    for index in 1..=255 {
+        let color = palette
+            .entries
+            .get(&index)
+            .map_or([0, 0, 0, 255], |entry| entry.color);
+        palette_content.extend_from_slice(&color);
+    }
+    palette_content.extend_from_slice(&[0, 0, 0, 255]);
+
+    let mut children = vec![];
+    write_chunk(&mut children, b"SIZE", &size_content, &[]);
+    write_chunk(&mut children, b"XYZI", &voxels_content, &[]);
+    write_chunk(&mut children, b"RGBA", &palette_content, &[]);
+
+    let mut output = b"VOX ".to_vec();
+    output.extend_from_slice(&VOX_VERSION.to_le_bytes());
+    write_chunk(&mut output, b"MAIN", &[], &children);
+    Ok(output)
+}
+
+/// Build a level from the MagicaVoxel file at `source` and write it to `destination`, `palette`
+/// defaults to the palette of the assets folder.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn import_vox_file(
+    source: &Path,
+    destination: &Path,
+    palette: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let palette = VoxPalette::read(palette)?;
+    let bytes = std::fs::read(source)?;
+    let template = level_from_vox(&bytes, &source.display().to_string(), &palette)?;
+
+    let ron_string = ron::ser::to_string_pretty(&template, ron::ser::PrettyConfig::default())?;
+    std::fs::write(destination, ron_string)?;
+    Ok(())
+}
+
+/// Write the entities of the level file at `source` as a MagicaVoxel file to `destination`,
+/// `palette` defaults to the palette of the assets folder.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_vox_file(
+    source: &Path,
+    destination: &Path,
+    palette: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let palette = VoxPalette::read(palette)?;
+    let bytes = std::fs::read(source)?;
+    let template = super::level_template::parse_level(&bytes, &source.display().to_string())?;
+
+    std::fs::write(destination, level_to_vox(&template, &palette)?)?;
+    Ok(())
+}
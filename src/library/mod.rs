@@ -2,6 +2,8 @@ use bevy::{gltf::Gltf, prelude::*, utils::HashMap};
 use bevy_asset_loader::prelude::{AssetCollection, AssetCollectionApp};
 use bevy_kira_audio::AudioSource;
 
+use crate::level::{level_pack::LevelPack, model_catalog::ModelCatalog};
+
 pub struct LibraryPlugin;
 
 impl Plugin for LibraryPlugin {
@@ -29,6 +31,11 @@ pub struct GameAssets {
 pub struct AssetLibrary {
     #[asset(path = "models", collection(typed, mapped))]
     pub models: HashMap<String, Handle<Gltf>>,
+    #[asset(path = "packs", collection(typed, mapped))]
+    pub packs: HashMap<String, Handle<LevelPack>>,
+    /// Describes how the models are placed in levels.
+    #[asset(path = "models.catalog")]
+    pub catalog: Handle<ModelCatalog>,
 }
 
 pub fn load_assets(
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use iyes_loopless::{
+    prelude::{AppLooplessStateExt, ConditionSet},
+    state::NextState,
+};
+
+use crate::{despawn_with_system, level::level_load_error::LevelLoadError, GameState};
+
+use super::{button_interact_visual_system, MenuStyles};
+
+/// Shows why a level could not be loaded, then goes back to the main menu.
+pub struct LevelErrorMenuPlugin;
+
+impl Plugin for LevelErrorMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::LevelError, setup_camera)
+            .add_enter_system(GameState::LevelError, setup_menu)
+            .add_exit_system(GameState::LevelError, despawn_with_system::<LevelErrorMenu>)
+            .add_exit_system(GameState::LevelError, remove_error_system)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::LevelError)
+                    .with_system(back_on_escape)
+                    .with_system(button_interact_visual_system)
+                    .with_system(on_back_button_interact_system)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct LevelErrorMenu;
+
+#[derive(Component)]
+struct BackButton;
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), LevelErrorMenu));
+}
+
+fn remove_error_system(mut commands: Commands) {
+    commands.remove_resource::<LevelLoadError>();
+}
+
+fn back_on_escape(mut commands: Commands, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        commands.insert_resource(NextState(GameState::MainMenu));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn on_back_button_interact_system(
+    mut commands: Commands,
+    query: Query<&Interaction, (Changed<Interaction>, With<Button>, With<BackButton>)>,
+) {
+    for interaction in query.iter() {
+        if *interaction == Interaction::Clicked {
+            commands.insert_resource(NextState(GameState::MainMenu));
+        }
+    }
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    menu_styles: Res<MenuStyles>,
+    error: Option<Res<LevelLoadError>>,
+) {
+    let message = error
+        .map(|error| error.to_string())
+        .unwrap_or_else(|| "Unknown error".to_owned());
+
+    let menu = commands
+        .spawn((
+            NodeBundle {
+                background_color: BackgroundColor(Color::NONE),
+                style: menu_styles.layout_node_style.clone(),
+                ..Default::default()
+            },
+            LevelErrorMenu,
+        ))
+        .id();
+
+    let title = commands
+        .spawn(TextBundle {
+            text: Text::from_section("Could not load the level", menu_styles.title_style.clone()),
+            style: menu_styles.button_style.clone(),
+            ..Default::default()
+        })
+        .id();
+
+    let details = commands
+        .spawn(TextBundle {
+            text: Text::from_section(message, menu_styles.button_text_style.clone()),
+            style: menu_styles.button_style.clone(),
+            ..Default::default()
+        })
+        .id();
+
+    let back_button = commands
+        .spawn((
+            ButtonBundle {
+                style: menu_styles.button_style.clone(),
+                background_color: BackgroundColor(Color::NONE),
+                ..Default::default()
+            },
+            BackButton,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    "Back to Main Menu",
+                    menu_styles.button_text_style.clone(),
+                ),
+                ..Default::default()
+            });
+        })
+        .id();
+
+    commands
+        .entity(menu)
+        .push_children(&[title, details, back_button]);
+}
@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 
+pub mod level_error_menu;
 pub mod main_menu;
 pub mod select_level_menu;
 
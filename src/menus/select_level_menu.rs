@@ -4,7 +4,16 @@ use iyes_loopless::{
     state::NextState,
 };
 
-use crate::{despawn_with_system, level::levels::LEVELS, GameState};
+use crate::{
+    despawn_with_system,
+    gameplay::score_plugin::Progress,
+    level::{
+        level_pack::{ActivePack, LevelPack},
+        level_template::{LevelTemplate, MAX_DIFFICULTY},
+    },
+    library::AssetLibrary,
+    GameState,
+};
 
 use super::{button_interact_visual_system, MenuStyles};
 
@@ -25,6 +34,9 @@ impl Plugin for SelectLevelMenuPlugin {
                     .with_system(button_interact_visual_system)
                     .with_system(on_back_button_interact_system)
                     .with_system(on_level_button_interact_system)
+                    .with_system(on_pack_button_interact_system)
+                    .with_system(populate_level_list_system)
+                    .with_system(update_level_labels_system)
                     .into(),
             );
     }
@@ -43,12 +55,70 @@ struct SelectLevelMenu;
 #[derive(Component)]
 struct BackButton;
 
+#[derive(Component)]
+struct PackButton;
+
+#[derive(Component)]
+struct PackLabel;
+
+/// Holds the worlds and levels of the pack it shows.
+#[derive(Component, Default)]
+struct LevelList {
+    shown_pack: Option<String>,
+}
+
 #[derive(Component)]
 struct LevelButton(usize);
 
+/// The text of a level button, filled with the level metadata once the level is loaded.
+#[derive(Component)]
+struct LevelLabel {
+    index: usize,
+    asset_path: String,
+    template: Handle<LevelTemplate>,
+}
+
+/// The index of the level to start in the active pack.
 #[derive(Resource)]
 pub struct NextLevel(pub usize);
 
+fn level_label(index: usize, template: Option<&LevelTemplate>, stars: Option<u8>) -> String {
+    let metadata = template.map(|template| &template.metadata);
+
+    let mut label = match metadata.and_then(|metadata| metadata.title.as_ref()) {
+        Some(title) => format!("{}. {}", index, title),
+        None => format!("Level {}", index),
+    };
+
+    if let Some(difficulty) = metadata.and_then(|metadata| metadata.difficulty) {
+        label.push_str(&format!(" ({}/{})", difficulty, MAX_DIFFICULTY));
+    }
+
+    if let Some(stars) = stars {
+        label.push_str(&format!(" - {}/3 stars", stars));
+    }
+
+    label
+}
+
+fn update_level_labels_system(
+    progress: Res<Progress>,
+    level_templates: Res<Assets<LevelTemplate>>,
+    mut label_query: Query<(&mut Text, &LevelLabel)>,
+) {
+    for (mut text, level) in &mut label_query {
+        let label = level_label(
+            level.index,
+            level_templates.get(&level.template),
+            progress.stars(&level.asset_path),
+        );
+
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn on_back_button_interact_system(
     mut commands: Commands,
@@ -80,6 +150,153 @@ fn on_level_button_interact_system(
     }
 }
 
+/// Switch to the next pack of the packs folder.
+#[allow(clippy::type_complexity)]
+fn on_pack_button_interact_system(
+    library: Res<AssetLibrary>,
+    mut active_pack: ResMut<ActivePack>,
+    query: Query<&Interaction, (Changed<Interaction>, With<Button>, With<PackButton>)>,
+) {
+    for interaction in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let mut pack_paths: Vec<&String> = library.packs.keys().collect();
+        pack_paths.sort();
+
+        let next_index = pack_paths
+            .iter()
+            .position(|path| **path == active_pack.0)
+            .map_or(0, |index| (index + 1) % pack_paths.len());
+
+        if let Some(path) = pack_paths.get(next_index) {
+            active_pack.0 = (*path).clone();
+        }
+    }
+}
+
+/// Fill the list with the worlds of the active pack, once it is loaded.
+#[allow(clippy::too_many_arguments)]
+fn populate_level_list_system(
+    mut commands: Commands,
+    menu_styles: Res<MenuStyles>,
+    assets: Res<AssetServer>,
+    progress: Res<Progress>,
+    active_pack: Res<ActivePack>,
+    library: Res<AssetLibrary>,
+    packs: Res<Assets<LevelPack>>,
+    mut list_query: Query<(Entity, &mut LevelList)>,
+    mut pack_text_query: Query<&mut Text, With<PackLabel>>,
+) {
+    let Ok((list_entity, mut list)) = list_query.get_single_mut() else {
+        return;
+    };
+
+    if list.shown_pack.as_ref() == Some(&active_pack.0) {
+        return;
+    }
+
+    let Some(pack) = active_pack.get(&library, &packs) else {
+        return;
+    };
+
+    list.shown_pack = Some(active_pack.0.clone());
+
+    for mut text in &mut pack_text_query {
+        text.sections[0].value = format!("Pack: {}", pack.name);
+    }
+
+    let button_style = Style {
+        padding: UiRect::all(Val::Px(2.0)),
+        margin: UiRect::all(Val::Px(2.0)),
+        ..menu_styles.button_style
+    };
+
+    let locked_text_style = TextStyle {
+        color: Color::GRAY,
+        ..menu_styles.button_text_style.clone()
+    };
+
+    commands.entity(list_entity).despawn_descendants();
+
+    let mut children: Vec<Entity> = Vec::with_capacity(pack.level_count() + pack.worlds.len());
+    let mut level_index = 0;
+
+    for (world_index, world) in pack.worlds.iter().enumerate() {
+        let unlocked = pack.is_world_unlocked(world_index, &progress);
+
+        let world_title = if unlocked {
+            world.name.clone()
+        } else {
+            format!("{} (locked)", world.name)
+        };
+
+        children.push(
+            commands
+                .spawn(TextBundle {
+                    text: Text::from_section(world_title, menu_styles.button_text_style.clone()),
+                    style: button_style.clone(),
+                    ..Default::default()
+                })
+                .id(),
+        );
+
+        for asset_path in &world.levels {
+            let label = LevelLabel {
+                index: level_index,
+                asset_path: asset_path.clone(),
+                template: assets.load(asset_path),
+            };
+
+            let text_style = if unlocked {
+                menu_styles.button_text_style.clone()
+            } else {
+                locked_text_style.clone()
+            };
+
+            // Locked levels are listed without a button.
+            let level = if unlocked {
+                commands
+                    .spawn((
+                        ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: BackgroundColor(Color::NONE),
+                            ..Default::default()
+                        },
+                        LevelButton(level_index),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            TextBundle {
+                                text: Text::from_section("", text_style),
+                                ..Default::default()
+                            },
+                            label,
+                        ));
+                    })
+                    .id()
+            } else {
+                commands
+                    .spawn((
+                        TextBundle {
+                            text: Text::from_section("", text_style),
+                            style: button_style.clone(),
+                            ..Default::default()
+                        },
+                        label,
+                    ))
+                    .id()
+            };
+
+            children.push(level);
+            level_index += 1;
+        }
+    }
+
+    commands.entity(list_entity).push_children(&children);
+}
+
 fn setup_menu(mut commands: Commands, menu_styles: Res<MenuStyles>) {
     let button_style = Style {
         padding: UiRect::all(Val::Px(2.0)),
@@ -98,53 +315,62 @@ fn setup_menu(mut commands: Commands, menu_styles: Res<MenuStyles>) {
         ))
         .id();
 
-    let mut buttons: Vec<Entity> = Vec::with_capacity(LEVELS.len() + 1);
-
-    for i in 0..LEVELS.len() {
-        buttons.push(
-            commands
-                .spawn((
-                    ButtonBundle {
-                        style: button_style.clone(),
-                        background_color: BackgroundColor(Color::NONE),
-                        ..Default::default()
-                    },
-                    LevelButton(i),
-                ))
-                .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            format!("Level {}", i),
-                            menu_styles.button_text_style.clone(),
-                        ),
-                        ..Default::default()
-                    });
-                })
-                .id(),
-        );
-    }
-
-    buttons.push(
-        commands
-            .spawn((
-                ButtonBundle {
-                    style: button_style,
-                    background_color: BackgroundColor(Color::NONE),
+    let pack_button = commands
+        .spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: BackgroundColor(Color::NONE),
+                ..Default::default()
+            },
+            PackButton,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section("Pack", menu_styles.button_text_style.clone()),
                     ..Default::default()
                 },
-                BackButton,
-            ))
-            .with_children(|parent| {
-                parent.spawn(TextBundle {
-                    text: Text::from_section(
-                        "Back to Main Menu",
-                        menu_styles.button_text_style.clone(),
-                    ),
+                PackLabel,
+            ));
+        })
+        .id();
+
+    let level_list = commands
+        .spawn((
+            NodeBundle {
+                background_color: BackgroundColor(Color::NONE),
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
                     ..Default::default()
-                });
-            })
-            .id(),
-    );
+                },
+                ..Default::default()
+            },
+            LevelList::default(),
+        ))
+        .id();
+
+    let back_button = commands
+        .spawn((
+            ButtonBundle {
+                style: button_style,
+                background_color: BackgroundColor(Color::NONE),
+                ..Default::default()
+            },
+            BackButton,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    "Back to Main Menu",
+                    menu_styles.button_text_style.clone(),
+                ),
+                ..Default::default()
+            });
+        })
+        .id();
 
-    commands.entity(menu).push_children(&buttons);
+    commands
+        .entity(menu)
+        .push_children(&[pack_button, level_list, back_button]);
 }
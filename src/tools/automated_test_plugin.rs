@@ -4,7 +4,9 @@ use bevy::{prelude::*, time::FixedTimestep};
 
 use crate::{
     gameplay::level_plugin::{load_level_system, StartLevelEventWithLevelAssetPath},
-    gameplay::{level_plugin::LevelStages, movement_plugin::MoveCommandEvent},
+    gameplay::{
+        level_plugin::LevelStages, movement_plugin::MoveCommandEvent, snake_plugin::Player,
+    },
 };
 
 #[derive(Clone)]
@@ -68,7 +70,7 @@ fn moc_player_input(
         return;
     };
 
-    move_command_event.send(MoveCommandEvent(next_move.0));
+    move_command_event.send(MoveCommandEvent(next_move.0, Player::One));
 }
 
 fn start_test_case(
@@ -25,6 +25,8 @@ pub(crate) enum CameraSystem {
 pub struct FlycamControls {
     pub yaw: f32,
     pub pitch: f32,
+    /// The axis of the yaw, opposite to the gravity of the level.
+    pub up: Vec3,
     pub sensitivity: f32,
     pub enable_movement: bool,
     pub enable_look: bool,
@@ -42,6 +44,7 @@ impl Default for FlycamControls {
         Self {
             yaw: Default::default(),
             pitch: Default::default(),
+            up: Vec3::Y,
             sensitivity: 1.0,
             enable_movement: true,
             enable_look: true,
@@ -58,10 +61,25 @@ impl Default for FlycamControls {
 
 impl FlycamControls {
     pub fn set_transform(&mut self, transform: &Transform) {
-        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let local_rotation = self.up_rotation().inverse() * transform.rotation;
+        let (yaw, pitch, _) = local_rotation.to_euler(EulerRot::YXZ);
         self.yaw = yaw;
         self.pitch = pitch;
     }
+
+    /// Turn the controls around the up direction of `gravity`, keeping the camera where it is.
+    pub fn set_up(&mut self, gravity: IVec3, transform: &Transform) {
+        self.up = -gravity.as_vec3();
+        self.set_transform(transform);
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.up_rotation() * Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    fn up_rotation(&self) -> Quat {
+        Quat::from_rotation_arc(Vec3::Y, self.up)
+    }
 }
 
 fn camera_movement(
@@ -133,7 +151,7 @@ fn camera_look(
         .pitch
         .clamp(-std::f32::consts::PI / 2.0, std::f32::consts::PI / 2.0);
 
-    transform.rotation = Quat::from_euler(EulerRot::YXZ, flycam.yaw, flycam.pitch, 0.0);
+    transform.rotation = flycam.rotation();
 }
 
 fn toggle_cursor(keyboard_input: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
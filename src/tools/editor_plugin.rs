@@ -1,6 +1,7 @@
-use std::{f32::consts::PI, fs::File, io::Write};
+use std::{f32::consts::PI, fs::File, io::Write, path::Path};
 
 use bevy::{gltf::Gltf, prelude::*, scene::SceneInstance, tasks::IoTaskPool};
+use bevy_egui::EguiContext;
 use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
 use bevy_prototype_debug_lines::DebugLinesMesh;
 use bevy_reflect::Reflect;
@@ -18,21 +19,28 @@ use crate::{
             clear_level_runtime_resources_system, spawn_level_entities_system,
             CurrentLevelMetadata, LevelLoadedEvent,
         },
+        npc_plugin::{NpcSnake, NpcSnakeTemplate},
         snake_plugin::{
             despawn_snake_part_system, update_snake_transforms_system, DespawnSnakePartEvent,
-            MaterialMeshBuilder, Snake, SnakePart,
+            MaterialMeshBuilder, MeshMaterialCache, Snake, SnakePart,
         },
+        wall_groups::{DirtyWallChunks, MergedWall, UnmergedWall},
     },
     level::{
+        ascii_level::{level_to_ascii, ASCII_LEVEL_EXTENSION},
         level_instance::{LevelGridEntity, LevelInstance},
         level_template::{
-            EntityTemplate, LevelTemplate, LoadedLevel, LoadingLevel, Model, ModelId,
+            level_file_path, DecorationTemplate, DefaultModel, EntityTemplate, LevelTemplate,
+            LoadedLevel, LoadingLevel, Model, ModelId, PropertyValue, MAX_DIFFICULTY,
         },
+        migrations::CURRENT_LEVEL_VERSION,
+        model_catalog::{occupied_cells, Footprint, ModelCatalog, ModelPivot},
+        vox::{entities_from_vox, level_to_vox, VoxPalette, VOX_EXTENSION},
     },
     library::{AssetLibrary, GameAssets},
     tools::{
         cameras::{camera_3d_free, EditorCamera},
-        picking::PickingCameraBundle,
+        picking::{DefaultHighlighting, Highlighting, PickingCamera, PickingCameraBundle},
     },
     utils::ray_from_screen_space,
     GameState,
@@ -45,16 +53,36 @@ use super::{
 
 pub struct EditorPlugin;
 
+/// The distance decorations move by per key press.
+const DECORATION_STEP: f32 = 0.25;
+const DECORATION_SCALE_STEP: f32 = 1.1;
+
+/// What a click adds to the level.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Default)]
+enum PlacementMode {
+    #[default]
+    Grid,
+    Decoration,
+}
+
 #[derive(Resource, InspectorOptions, Reflect)]
 #[reflect(InspectorOptions)]
 struct EditorState {
     insert_entity_type: EntityType,
+    placement_mode: PlacementMode,
+    /// Path of the model of the decorations to add, empty until one is picked.
+    decoration_model: String,
+    /// The name of the property the inspector adds.
+    new_property_key: String,
 }
 
 impl Default for EditorState {
     fn default() -> Self {
         Self {
             insert_entity_type: EntityType::Wall,
+            placement_mode: PlacementMode::Grid,
+            decoration_model: String::new(),
+            new_property_key: String::new(),
         }
     }
 }
@@ -87,15 +115,22 @@ impl Plugin for EditorPlugin {
                     .with_system(choose_entity_to_add_system)
                     .with_system(add_pickable_to_level_entities_system)
                     .with_system(add_entity_on_click_system)
+                    .with_system(add_decoration_on_click_system)
+                    .with_system(placement_window_system)
+                    .with_system(entity_inspector_window_system)
                     .with_system(select_parent_level_entity_system)
+                    .with_system(select_wall_of_group_system)
+                    .with_system(unmerge_selected_walls_system)
                     .with_system(delete_selected_entity_system)
                     .with_system(create_new_level_system)
                     .with_system(update_snake_transforms_system)
                     .with_system(move_selected_grid_entity)
+                    .with_system(move_selected_decoration_system)
+                    .with_system(scale_selected_decoration_system)
                     .with_system(move_selected_snake_system)
                     .with_system(resize_selected_snake_system)
                     .with_system(despawn_snake_part_system)
-                    .with_system(assign_model_to_wall)
+                    .with_system(assign_catalog_model_system)
                     .with_system(rotate_selected_entity_system)
                     // .with_system(ui_editor)
                     .into(),
@@ -105,6 +140,8 @@ impl Plugin for EditorPlugin {
                     .run_in_state(GameState::Editor)
                     .run_if_resource_exists::<CurrentLevelMetadata>()
                     .with_system(save_level_system)
+                    .with_system(import_vox_system)
+                    .with_system(level_metadata_window_system)
                     .with_system(stop_editor_system)
                     .into(),
             );
@@ -174,6 +211,13 @@ fn choose_entity_to_add_system(
     keyboard: Res<Input<KeyCode>>,
     mut editor_state: ResMut<EditorState>,
 ) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        editor_state.placement_mode = match editor_state.placement_mode {
+            PlacementMode::Grid => PlacementMode::Decoration,
+            PlacementMode::Decoration => PlacementMode::Grid,
+        };
+    }
+
     if keyboard.just_pressed(KeyCode::Key7) {
         editor_state.insert_entity_type = EntityType::Goal;
     } else if keyboard.just_pressed(KeyCode::Key6) {
@@ -188,6 +232,10 @@ fn choose_entity_to_add_system(
         editor_state.insert_entity_type = EntityType::Trigger;
     } else if keyboard.just_pressed(KeyCode::Key5) {
         editor_state.insert_entity_type = EntityType::Snake;
+    } else if keyboard.just_pressed(KeyCode::Key8) {
+        editor_state.insert_entity_type = EntityType::BreakableWall;
+    } else if keyboard.just_pressed(KeyCode::Key9) {
+        editor_state.insert_entity_type = EntityType::GravitySwitch;
     }
 }
 
@@ -202,11 +250,16 @@ fn add_entity_on_click_system(
     mut level_instance: ResMut<LevelInstance>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
     gltfs: Res<Assets<Gltf>>,
     snakes: Query<&Snake>,
     assets: Res<GameAssets>,
+    properties: Query<&EntityProperties>,
 ) {
-    if !keyboard.pressed(KeyCode::LControl) || !buttons.just_pressed(MouseButton::Left) {
+    if editor_state.placement_mode != PlacementMode::Grid
+        || !keyboard.pressed(KeyCode::LControl)
+        || !buttons.just_pressed(MouseButton::Left)
+    {
         return;
     }
 
@@ -221,6 +274,7 @@ fn add_entity_on_click_system(
     let mut mesh_builder = MaterialMeshBuilder {
         meshes: meshes.as_mut(),
         materials: materials.as_mut(),
+        cache: cache.as_mut(),
     };
 
     let Some(position) = level_instance.find_first_free_cell_on_ray(ray) else {
@@ -230,9 +284,13 @@ fn add_entity_on_click_system(
     let id = match editor_state.insert_entity_type {
         EntityType::Food => spawn_food(&mut mesh_builder, &mut commands, &position),
         EntityType::Spike => spawn_spike(&mut mesh_builder, &mut commands, &position),
-        EntityType::Wall => {
-            spawn_wall(&mut mesh_builder, &mut commands, &position, assets.as_ref())
-        }
+        EntityType::Wall => spawn_wall(&mut commands, &position, assets.as_ref()),
+        EntityType::BreakableWall => spawn_breakable_wall(
+            &mut mesh_builder,
+            &mut commands,
+            &position,
+            BreakableWallComponent::default(),
+        ),
         EntityType::Box => spawn_box(&mut mesh_builder, &mut commands, &position),
         EntityType::Trigger => spawn_trigger(&mut mesh_builder, &mut commands, &position),
         EntityType::Snake => spawn_snake(
@@ -243,6 +301,9 @@ fn add_entity_on_click_system(
             snakes.iter().len() as i32,
         ),
         EntityType::Goal => spawn_goal(&mut commands, &position, &assets, &gltfs),
+        EntityType::GravitySwitch => {
+            spawn_gravity_switch(&mut mesh_builder, &mut commands, &position)
+        }
     };
 
     level_instance.mark_position_occupied(
@@ -251,6 +312,218 @@ fn add_entity_on_click_system(
     );
 
     commands.entity(id).insert(PickableBundle::default());
+
+    // New entities get an id right away so the others can refer to them.
+    if editor_state.insert_entity_type != EntityType::Snake {
+        let id_in_level = properties
+            .iter()
+            .map(|properties| properties.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        commands.entity(id).insert(EntityProperties {
+            id: id_in_level,
+            ..default()
+        });
+    }
+}
+
+/// Decorations are placed in the first free cell under the cursor, then moved freely.
+#[allow(clippy::too_many_arguments)]
+fn add_decoration_on_click_system(
+    buttons: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    editor_state: Res<EditorState>,
+    windows: Res<Windows>,
+    mut commands: Commands,
+    camera: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    level_instance: Res<LevelInstance>,
+    library: Res<AssetLibrary>,
+    gltfs: Res<Assets<Gltf>>,
+) {
+    if editor_state.placement_mode != PlacementMode::Decoration
+        || !keyboard.pressed(KeyCode::LControl)
+        || !buttons.just_pressed(MouseButton::Left)
+    {
+        return;
+    }
+
+    let window = windows.get_primary().unwrap();
+    let Some(mouse_position) = window.cursor_position() else {
+        return;
+    };
+
+    let (camera, camera_transform) = camera.single();
+    let ray = ray_from_screen_space(mouse_position, camera, camera_transform);
+
+    let Some(position) = level_instance.find_first_free_cell_on_ray(ray) else {
+        return;
+    };
+
+    let decoration = DecorationTemplate {
+        model: editor_state.decoration_model.clone(),
+        translation: position.as_vec3(),
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    let Some(id) = spawn_decoration(&mut commands, &decoration, &library, &gltfs) else {
+        warn!("Pick a decoration model before placing decorations");
+        return;
+    };
+
+    commands.entity(id).insert(PickableBundle::default());
+}
+
+/// The name of the model in the catalog, or its path.
+fn model_name<'a>(catalog: &'a ModelCatalog, path: &'a str) -> &'a str {
+    catalog.get(path).map_or(path, |model| model.name.as_str())
+}
+
+fn placement_window_system(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor_state: ResMut<EditorState>,
+    library: Res<AssetLibrary>,
+    catalogs: Res<Assets<ModelCatalog>>,
+) {
+    let catalog = ModelCatalog::of_library(&library, &catalogs);
+
+    let mut models: Vec<&String> = library.models.keys().collect();
+    models.sort();
+
+    let insert_entity_type = editor_state.insert_entity_type;
+    let mut placement_mode = editor_state.placement_mode;
+    let mut decoration_model = editor_state.decoration_model.clone();
+
+    egui::Window::new("Placement").show(egui_context.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut placement_mode, PlacementMode::Grid, "Grid entities");
+            ui.radio_value(
+                &mut placement_mode,
+                PlacementMode::Decoration,
+                "Decorations",
+            );
+        });
+
+        ui.add_enabled_ui(placement_mode == PlacementMode::Decoration, |ui| {
+            egui::ComboBox::from_label("Model")
+                .selected_text(model_name(catalog, &decoration_model))
+                .show_ui(ui, |ui| {
+                    for model in models {
+                        let name = model_name(catalog, model);
+                        ui.selectable_value(&mut decoration_model, model.clone(), name);
+                    }
+                });
+        });
+
+        // The palette of the entity type to add, the number keys give the selected entities
+        // the models of the palette of their type.
+        ui.separator();
+        ui.label(format!("{:?} models", insert_entity_type));
+        for (index, (_, model)) in catalog.models_for(insert_entity_type).enumerate().take(9) {
+            ui.label(format!("{}: {}", index + 1, model.name));
+        }
+    });
+
+    if editor_state.placement_mode != placement_mode {
+        editor_state.placement_mode = placement_mode;
+    }
+    if editor_state.decoration_model != decoration_model {
+        editor_state.decoration_model = decoration_model;
+    }
+}
+
+/// Edit a property value, returns false when the property is removed.
+fn property_value_edit(ui: &mut egui::Ui, key: &str, value: &mut PropertyValue) -> bool {
+    let mut keep = true;
+
+    ui.horizontal(|ui| {
+        ui.label(key);
+        match value {
+            PropertyValue::Int(value) => {
+                ui.add(egui::DragValue::new(value));
+            }
+            PropertyValue::String(value) => {
+                ui.text_edit_singleline(value);
+            }
+            PropertyValue::Vector(value) => {
+                ui.add(egui::DragValue::new(&mut value.x).prefix("x "));
+                ui.add(egui::DragValue::new(&mut value.y).prefix("y "));
+                ui.add(egui::DragValue::new(&mut value.z).prefix("z "));
+            }
+            PropertyValue::Entity(id) => {
+                ui.add(egui::DragValue::new(id).prefix("entity "));
+            }
+        }
+        keep = !ui.small_button("x").clicked();
+    });
+
+    keep
+}
+
+/// Shows the id and edits the properties of the selected grid entity.
+fn entity_inspector_window_system(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor_state: ResMut<EditorState>,
+    mut selection: Query<(&GridEntity, &mut EntityProperties, &Selection)>,
+) {
+    let mut selected = selection
+        .iter_mut()
+        .filter(|(_, _, selection)| selection.selected());
+
+    let (Some((grid_entity, mut properties, _)), None) = (selected.next(), selected.next()) else {
+        return;
+    };
+
+    let mut edited = properties.clone();
+    let mut new_property_key = editor_state.new_property_key.clone();
+
+    egui::Window::new("Entity").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{:?} {} at {}",
+            grid_entity.entity_type, edited.id, grid_entity.position
+        ));
+        ui.separator();
+
+        edited
+            .values
+            .retain(|key, value| property_value_edit(ui, key, value));
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("New property");
+            ui.text_edit_singleline(&mut new_property_key);
+        });
+
+        let key = new_property_key.trim().to_owned();
+        ui.add_enabled_ui(!key.is_empty() && !edited.values.contains_key(&key), |ui| {
+            ui.horizontal(|ui| {
+                let value = if ui.button("Int").clicked() {
+                    Some(PropertyValue::Int(0))
+                } else if ui.button("Text").clicked() {
+                    Some(PropertyValue::String(String::new()))
+                } else if ui.button("Vector").clicked() {
+                    Some(PropertyValue::Vector(IVec3::ZERO))
+                } else if ui.button("Entity").clicked() {
+                    Some(PropertyValue::Entity(0))
+                } else {
+                    None
+                };
+
+                if let Some(value) = value {
+                    edited.values.insert(key.clone(), value);
+                    new_property_key.clear();
+                }
+            });
+        });
+    });
+
+    if *properties != edited {
+        *properties = edited;
+    }
+    if editor_state.new_property_key != new_property_key {
+        editor_state.new_property_key = new_property_key;
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -293,6 +566,81 @@ fn select_parent_level_entity_system(
     }
 }
 
+/// Walls merged in a group are picked through the mesh of the group, the clicked face tells
+/// which wall to select.
+fn select_wall_of_group_system(
+    level_instance: Res<LevelInstance>,
+    picking_cameras: Query<&PickingCamera>,
+    groups: Query<Entity, With<WallGroup>>,
+    mut selections: Query<&mut Selection>,
+) {
+    for group in &groups {
+        let Ok(mut selection) = selections.get_mut(group) else {
+            continue;
+        };
+        if !selection.is_changed() || !selection.selected() {
+            continue;
+        }
+        selection.set_selected(false);
+
+        let intersection = picking_cameras
+            .iter()
+            .filter_map(|camera| camera.intersections().first())
+            .find(|(entity, _)| *entity == group);
+        let Some((_, intersection)) = intersection else {
+            continue;
+        };
+
+        let cell = (intersection.position() - intersection.normal() * 0.5)
+            .round()
+            .as_ivec3();
+        if let Some(wall) = level_instance.get(cell) {
+            if let Ok(mut selection) = selections.get_mut(wall.entity) {
+                selection.set_selected(true);
+            }
+        }
+    }
+}
+
+/// Selected walls leave their group so they can be highlighted, moved and edited alone.
+#[allow(clippy::type_complexity)]
+fn unmerge_selected_walls_system(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    highlighting: Res<DefaultHighlighting<StandardMaterial>>,
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    walls: Query<
+        (
+            Entity,
+            &GridEntity,
+            &Selection,
+            Option<&MergedWall>,
+            Option<&UnmergedWall>,
+        ),
+        Changed<Selection>,
+    >,
+) {
+    for (entity, grid_entity, selection, merged, unmerged) in &walls {
+        if selection.selected() && merged.is_some() {
+            commands.entity(entity).remove::<MergedWall>().insert((
+                UnmergedWall,
+                assets.cube_mesh.clone(),
+                highlighting.selected.clone(),
+                Highlighting {
+                    initial: assets.default_cube_material.clone(),
+                    hovered: None,
+                    pressed: None,
+                    selected: None,
+                },
+            ));
+            dirty_chunks.mark(grid_entity.position);
+        } else if !selection.selected() && unmerged.is_some() {
+            commands.entity(entity).remove::<UnmergedWall>();
+            dirty_chunks.mark(grid_entity.position);
+        }
+    }
+}
+
 fn select_move_direction(
     keyboard: &Input<KeyCode>,
     camera_transform: &GlobalTransform,
@@ -328,11 +676,18 @@ fn select_move_direction(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn move_selected_grid_entity(
     keyboard: Res<Input<KeyCode>>,
     mouse_input: Res<Input<MouseButton>>,
     mut level_instance: ResMut<LevelInstance>,
-    mut selection: Query<(&Selection, &mut GridEntity, &mut Transform)>,
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    mut selection: Query<(
+        &Selection,
+        &mut GridEntity,
+        &mut Transform,
+        Option<&Footprint>,
+    )>,
     camera: Query<&GlobalTransform, With<EditorCamera>>,
 ) {
     if mouse_input.pressed(MouseButton::Right) || !keyboard.pressed(KeyCode::LControl) {
@@ -346,16 +701,18 @@ fn move_selected_grid_entity(
 
     let mut moves = Vec::with_capacity(selection.iter().len());
 
-    for (selection, mut grid_entity, mut transform) in &mut selection {
+    for (selection, mut grid_entity, mut transform, footprint) in &mut selection {
         if !selection.selected() {
             continue;
         }
 
-        moves.push((
-            grid_entity.position,
-            grid_entity.position + direction,
-            *level_instance.get(grid_entity.position).unwrap(),
-        ));
+        let value = *level_instance.get(grid_entity.position).unwrap();
+        for cell in occupied_cells(grid_entity.position, footprint, transform.rotation) {
+            moves.push((cell, cell + direction, value));
+        }
+        if grid_entity.entity_type == EntityType::Wall {
+            dirty_chunks.mark(grid_entity.position);
+        }
 
         grid_entity.position += direction;
         transform.translation += direction.as_vec3();
@@ -370,6 +727,47 @@ fn move_selected_grid_entity(
     }
 }
 
+fn move_selected_decoration_system(
+    keyboard: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut selection: Query<(&Selection, &mut Transform), With<Decoration>>,
+    camera: Query<&GlobalTransform, With<EditorCamera>>,
+) {
+    if mouse_input.pressed(MouseButton::Right) || !keyboard.pressed(KeyCode::LControl) {
+        return;
+    }
+
+    let camera_transform = camera.single();
+    let Some(direction) = select_move_direction(&keyboard, camera_transform) else {
+        return;
+    };
+
+    for (selection, mut transform) in &mut selection {
+        if selection.selected() {
+            transform.translation += DECORATION_STEP * direction.as_vec3();
+        }
+    }
+}
+
+fn scale_selected_decoration_system(
+    keyboard: Res<Input<KeyCode>>,
+    mut selection: Query<(&Selection, &mut Transform), With<Decoration>>,
+) {
+    let factor = if keyboard.just_pressed(KeyCode::PageUp) {
+        DECORATION_SCALE_STEP
+    } else if keyboard.just_pressed(KeyCode::PageDown) {
+        1.0 / DECORATION_SCALE_STEP
+    } else {
+        return;
+    };
+
+    for (selection, mut transform) in &mut selection {
+        if selection.selected() {
+            transform.scale *= factor;
+        }
+    }
+}
+
 fn move_selected_snake_system(
     keyboard: Res<Input<KeyCode>>,
     mouse_input: Res<Input<MouseButton>>,
@@ -414,11 +812,13 @@ fn move_selected_snake_system(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn resize_selected_snake_system(
     mut commands: Commands,
     keyboard: Res<Input<KeyCode>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<MeshMaterialCache>,
     mut level_instance: ResMut<LevelInstance>,
     mut selection: Query<(Entity, &Selection, &mut Snake)>,
     mut despawn_snake_part: EventWriter<DespawnSnakePartEvent>,
@@ -426,6 +826,7 @@ fn resize_selected_snake_system(
     let mut mesh_builder = MaterialMeshBuilder {
         meshes: meshes.as_mut(),
         materials: materials.as_mut(),
+        cache: cache.as_mut(),
     };
 
     for (entity, selection, mut snake) in &mut selection {
@@ -465,49 +866,92 @@ fn resize_selected_snake_system(
     }
 }
 
+/// Grid entities turn by quarter turns around their grid position, with their footprint.
+/// Decorations turn by smaller steps.
+#[allow(clippy::type_complexity)]
 fn rotate_selected_entity_system(
     keyboard: Res<Input<KeyCode>>,
-    mut selection: Query<(&mut Transform, &Selection), With<GridEntity>>,
+    mut level_instance: ResMut<LevelInstance>,
+    mut grid_entities: Query<(&mut Transform, &GridEntity, Option<&Footprint>, &Selection)>,
+    mut decorations: Query<(&mut Transform, &Selection), (With<Decoration>, Without<GridEntity>)>,
 ) {
-    let rotation = if keyboard.just_pressed(KeyCode::Minus) {
-        Some(Quat::from_euler(EulerRot::XYZ, 0.0, 0.5 * PI, 0.0))
+    let sign = if keyboard.just_pressed(KeyCode::Minus) {
+        1.0
     } else if keyboard.just_pressed(KeyCode::Equals) {
-        Some(Quat::from_euler(EulerRot::XYZ, 0.0, -0.5 * PI, 0.0))
+        -1.0
     } else {
-        None
-    };
-
-    let Some(rotation) = rotation else {
         return;
     };
 
-    for (mut transform, selection) in selection.iter_mut() {
+    let quarter_turn = Quat::from_euler(EulerRot::XYZ, 0.0, sign * 0.5 * PI, 0.0);
+
+    for (mut transform, grid_entity, footprint, selection) in grid_entities.iter_mut() {
         if !selection.selected() {
             continue;
         }
 
-        transform.rotate(rotation);
+        let position = grid_entity.position;
+        let old_cells = occupied_cells(position, footprint, transform.rotation);
+        let value = *level_instance.get(position).unwrap();
+
+        // Keep the pivot of the model at the same place relative to the grid position.
+        let offset = transform.translation - position.as_vec3();
+        transform.translation = position.as_vec3() + quarter_turn * offset;
+        transform.rotate(quarter_turn);
+
+        for cell in old_cells {
+            level_instance.set_empty(cell);
+        }
+        for cell in occupied_cells(position, footprint, transform.rotation) {
+            level_instance.mark_position_occupied(cell, value);
+        }
+    }
+
+    for (mut transform, selection) in decorations.iter_mut() {
+        if selection.selected() {
+            transform.rotate(Quat::from_euler(EulerRot::XYZ, 0.0, sign * PI / 12.0, 0.0));
+        }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn delete_selected_entity_system(
     mut commands: Commands,
     keyboard: Res<Input<KeyCode>>,
     mut level_instance: ResMut<LevelInstance>,
-    selection: Query<(Entity, &GridEntity, &Selection)>,
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    selection: Query<(
+        Entity,
+        &GridEntity,
+        &Transform,
+        Option<&Footprint>,
+        &Selection,
+    )>,
+    decorations: Query<(Entity, &Selection), With<Decoration>>,
 ) {
     if !keyboard.just_pressed(KeyCode::Back) {
         return;
     }
 
-    for (entity, grid_entity, selection) in &selection {
+    for (entity, grid_entity, transform, footprint, selection) in &selection {
         if !selection.selected() {
             continue;
         }
 
-        level_instance.set_empty(grid_entity.position);
+        for cell in occupied_cells(grid_entity.position, footprint, transform.rotation) {
+            level_instance.set_empty(cell);
+        }
+        if grid_entity.entity_type == EntityType::Wall {
+            dirty_chunks.mark(grid_entity.position);
+        }
         commands.entity(entity).despawn();
     }
+
+    for (entity, selection) in &decorations {
+        if selection.selected() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
 }
 
 trait OptionSelector {
@@ -542,33 +986,79 @@ impl OptionSelector for Input<KeyCode> {
     }
 }
 
-fn assign_model_to_wall(
+/// Number keys draw the selected entities with the models of the catalog allowed for them.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn assign_catalog_model_system(
     mut commands: Commands,
     keyboard: Res<Input<KeyCode>>,
-    selection: Query<(Entity, &GridEntity, &Selection)>,
+    mut level_instance: ResMut<LevelInstance>,
+    mut dirty_chunks: ResMut<DirtyWallChunks>,
+    selection: Query<(
+        Entity,
+        &GridEntity,
+        &Transform,
+        Option<&Footprint>,
+        &Selection,
+    )>,
     gltfs: Res<Assets<Gltf>>,
     library: Res<AssetLibrary>,
+    catalogs: Res<Assets<ModelCatalog>>,
 ) {
-    let Some(mesh_index) = keyboard.option_pressed() else {
+    let Some(option) = keyboard.option_pressed() else {
         return;
     };
+    let catalog = ModelCatalog::of_library(&library, &catalogs);
+
+    for (entity, grid_entity, transform, footprint, selection) in &selection {
+        if !selection.selected() {
+            continue;
+        }
 
-    let wall_kitchen_walls = vec![
-        "models/kitchen1.gltf",
-        "models/kitchen2.gltf",
-        "models/kitchen3.gltf",
-    ];
+        let Some((path, catalog_model)) = option
+            .checked_sub(1)
+            .and_then(|index| catalog.models_for(grid_entity.entity_type).nth(index))
+        else {
+            continue;
+        };
 
-    for (entity, grid_entity, selection) in &selection {
-        if !selection.selected() || grid_entity.entity_type != EntityType::Wall {
+        let Some(model) = library.models.get(path) else {
+            continue;
+        };
+        let Some(scene) = gltfs
+            .get(model)
+            .and_then(|gltf| gltf.scenes.first().cloned())
+        else {
+            warn!("{} has no scene", catalog_model.name);
+            continue;
+        };
+
+        let position = grid_entity.position;
+        let old_cells = occupied_cells(position, footprint, transform.rotation);
+        let new_footprint = catalog.footprint(path);
+        let new_cells = new_footprint.cells(position, catalog_model.rotation);
+
+        let blocked = new_cells.iter().any(|cell| {
+            level_instance
+                .get(*cell)
+                .map_or(false, |value| value.entity != entity)
+        });
+        if blocked {
+            warn!("{} doesn't fit at {}", catalog_model.name, position);
             continue;
         }
 
-        let model = library
-            .models
-            .get(wall_kitchen_walls[mesh_index - 1])
-            .unwrap();
-        let scene = gltfs.get(model).unwrap().scenes[0].clone();
+        for cell in old_cells {
+            level_instance.set_empty(cell);
+        }
+        if grid_entity.entity_type == EntityType::Wall {
+            dirty_chunks.mark(position);
+        }
+        for cell in new_cells {
+            level_instance.mark_position_occupied(
+                cell,
+                LevelGridEntity::new(entity, grid_entity.entity_type),
+            );
+        }
 
         commands
             .entity(entity)
@@ -578,16 +1068,20 @@ fn assign_model_to_wall(
                 Handle<Scene>,
                 ModelId,
                 SceneInstance,
+                MergedWall,
+                UnmergedWall,
             )>()
             .insert((
                 SceneBundle {
                     scene,
-                    transform: Transform::from_translation(grid_entity.position.as_vec3()),
+                    transform: catalog_model.transform(position, catalog_model.rotation),
                     ..default()
                 },
                 ModelId {
                     source_asset: model.clone(),
                 },
+                ModelPivot(catalog_model.pivot),
+                new_footprint,
             ));
     }
 }
@@ -604,18 +1098,19 @@ fn create_new_level(
         }
     }
 
-    let new_tempale = LevelTemplate {
+    let mut new_tempale = LevelTemplate {
         entities: walls
             .into_iter()
             .map(|position| EntityTemplate {
                 entity_type: EntityType::Wall,
-                model: Model::Default(EntityType::Wall.into()),
+                model: Model::Default(DefaultModel::Wall),
                 grid_position: position,
                 ..default()
             })
             .collect(),
         ..default()
     };
+    new_tempale.assign_missing_ids();
 
     commands.insert_resource(CurrentLevelMetadata {
         id: None,
@@ -649,28 +1144,163 @@ fn create_new_level_system(
     create_new_level(&mut level_loaded_event, &mut levels, &mut commands);
 }
 
+/// Edit an optional text, an empty text is `None`.
+fn optional_text_edit(ui: &mut egui::Ui, label: &str, value: &mut Option<String>, multiline: bool) {
+    let mut text = value.clone().unwrap_or_default();
+
+    ui.label(label);
+    let response = if multiline {
+        ui.text_edit_multiline(&mut text)
+    } else {
+        ui.text_edit_singleline(&mut text)
+    };
+
+    if response.changed() {
+        *value = (!text.is_empty()).then_some(text);
+    }
+}
+
+fn level_metadata_window_system(
+    mut egui_context: ResMut<EguiContext>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    mut levels: ResMut<Assets<LevelTemplate>>,
+) {
+    let Some(loaded_level) = loaded_level else {
+        return;
+    };
+
+    let Some(mut metadata) = levels
+        .get(&loaded_level.0)
+        .map(|level| level.metadata.clone())
+    else {
+        return;
+    };
+
+    egui::Window::new("Level metadata").show(egui_context.ctx_mut(), |ui| {
+        optional_text_edit(ui, "Title", &mut metadata.title, false);
+        optional_text_edit(ui, "Author", &mut metadata.author, false);
+        optional_text_edit(ui, "Description", &mut metadata.description, true);
+
+        let mut difficulty = metadata.difficulty.unwrap_or(0);
+        ui.add(egui::Slider::new(&mut difficulty, 0..=MAX_DIFFICULTY).text("Difficulty"));
+        metadata.difficulty = (difficulty > 0).then_some(difficulty);
+
+        // Tags and hints are cleaned up when saving, so the texts can be edited freely.
+        let mut tags = metadata.tags.join(",");
+        ui.label("Tags, separated by commas");
+        if ui.text_edit_singleline(&mut tags).changed() {
+            metadata.tags = tags.split(',').map(str::to_owned).collect();
+        }
+
+        let mut hints = metadata.hints.join("\n");
+        ui.label("Hints, one per line");
+        if ui.text_edit_multiline(&mut hints).changed() {
+            metadata.hints = hints.split('\n').map(str::to_owned).collect();
+        }
+    });
+
+    // Only touch the asset on changes, the editor saves the metadata from it.
+    if levels.get(&loaded_level.0).map(|level| &level.metadata) != Some(&metadata) {
+        if let Some(level) = levels.get_mut(&loaded_level.0) {
+            level.metadata = metadata;
+        }
+    }
+}
+
+/// Replace the entities of the level with the voxels of the `.vox` file next to it, the snakes
+/// and the rest of the level are reloaded from the level file.
+fn import_vox_system(
+    keyboard: Res<Input<KeyCode>>,
+    level_meta: Res<CurrentLevelMetadata>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    mut levels: ResMut<Assets<LevelTemplate>>,
+    mut level_loaded_event: EventWriter<LevelLoadedEvent>,
+    mut commands: Commands,
+    entities: Query<Entity, With<LevelEntity>>,
+) {
+    if !keyboard.pressed(KeyCode::LWin) || !keyboard.just_pressed(KeyCode::V) {
+        return;
+    }
+
+    let file = level_file_path(&level_meta.asset_path).with_extension(VOX_EXTENSION);
+
+    let vox_entities = std::fs::read(&file)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| {
+            let palette = VoxPalette::read(None).map_err(|error| error.to_string())?;
+            entities_from_vox(&bytes, &file.display().to_string(), &palette)
+                .map_err(|error| error.to_string())
+        });
+
+    let vox_entities = match vox_entities {
+        Ok(vox_entities) => vox_entities,
+        Err(error) => {
+            error!("Could not import {}: {}", file.display(), error);
+            return;
+        }
+    };
+
+    let mut template = LevelTemplate {
+        entities: vox_entities,
+        entity_lines: vec![],
+        ..loaded_level
+            .and_then(|level| levels.get(&level.0).cloned())
+            .unwrap_or_default()
+    };
+    template.assign_missing_ids();
+
+    despawn_entities::<LevelEntity>(&mut commands, entities);
+    commands.insert_resource(LoadedLevel(levels.add(template)));
+    commands.insert_resource(LevelInstance::new());
+    level_loaded_event.send(LevelLoadedEvent);
+}
+
 #[allow(clippy::too_many_arguments)]
 fn save_level_system(
     keyboard: Res<Input<KeyCode>>,
     level_meta: Res<CurrentLevelMetadata>,
-    snake_query: Query<&Snake>,
-    entities: Query<(&GridEntity, &Transform, Option<&ModelId>)>,
+    level_instance: Res<LevelInstance>,
+    snake_query: Query<&Snake, Without<NpcSnake>>,
+    npc_query: Query<(&Snake, &NpcSnake)>,
+    entities: Query<(
+        &GridEntity,
+        &Transform,
+        Option<&ModelId>,
+        Option<&EntityProperties>,
+    )>,
+    decorations: Query<(&Transform, &ModelId), With<Decoration>>,
     assets: Res<AssetServer>,
+    loaded_level: Option<Res<LoadedLevel>>,
+    level_templates: Res<Assets<LevelTemplate>>,
 ) {
     if !keyboard.pressed(KeyCode::LWin) || !keyboard.just_pressed(KeyCode::S) {
         return;
     }
 
-    let template = LevelTemplate {
+    // The fields that can't be derived from the level in the editor are kept from the file.
+    let mut base = loaded_level
+        .and_then(|level| level_templates.get(&level.0).cloned())
+        .unwrap_or_default();
+
+    let metadata = &mut base.metadata;
+    metadata.tags = metadata
+        .tags
+        .iter()
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    metadata.hints.retain(|hint| !hint.trim().is_empty());
+
+    let mut template = LevelTemplate {
+        version: CURRENT_LEVEL_VERSION,
         snakes: snake_query
             .iter()
             .map(|snake| snake.parts().clone().into())
             .collect(),
         entities: entities
             .into_iter()
-            .map(|(entity, transform, gltf)| EntityTemplate {
-                entity_type: entity.entity_type,
-                model: match gltf {
+            .filter_map(|(entity, transform, gltf, properties)| {
+                let model = match gltf {
                     Some(gltf) => Model::Asset(
                         assets
                             .get_handle_path(&gltf.source_asset)
@@ -680,22 +1310,98 @@ fn save_level_system(
                             .unwrap()
                             .to_owned(),
                     ),
-                    None => Model::Default(entity.entity_type.into()),
-                },
-                grid_position: entity.position,
-                rotation: transform.rotation,
+                    None => Model::Default(entity.entity_type.try_into().ok()?),
+                };
+
+                let properties = properties.cloned().unwrap_or_default();
+
+                Some(EntityTemplate {
+                    id: properties.id,
+                    entity_type: entity.entity_type,
+                    model,
+                    grid_position: entity.position,
+                    rotation: transform.rotation,
+                    properties: properties.values,
+                })
+            })
+            .collect(),
+        npc_snakes: npc_query
+            .iter()
+            .map(|(snake, npc)| NpcSnakeTemplate {
+                parts: snake.parts().clone().into(),
+                behaviour: npc.behaviour.clone(),
+            })
+            .collect(),
+        planar: level_instance.is_planar(),
+        decorations: decorations
+            .iter()
+            .filter_map(|(transform, model)| {
+                Some(DecorationTemplate {
+                    model: assets
+                        .get_handle_path(&model.source_asset)?
+                        .path()
+                        .to_str()?
+                        .to_owned(),
+                    translation: transform.translation,
+                    rotation: transform.rotation,
+                    scale: transform.scale,
+                })
             })
             .collect(),
+        ..base
+    };
+    template.assign_missing_ids();
+
+    // Shift also writes the level next to it as a text map, Alt as voxels.
+    let copy_extension = if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        Some(ASCII_LEVEL_EXTENSION)
+    } else if keyboard.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        Some(VOX_EXTENSION)
+    } else {
+        None
+    };
+
+    let level_asset_path = match copy_extension {
+        Some(extension) => Path::new(&level_meta.asset_path)
+            .with_extension(extension)
+            .to_string_lossy()
+            .into_owned(),
+        None => level_meta.asset_path.clone(),
     };
 
-    let ron_string = ron::ser::to_string_pretty(&template, PrettyConfig::default()).unwrap();
-    let level_asset_path = level_meta.asset_path.clone();
+    let level_bytes = if level_asset_path.ends_with(ASCII_LEVEL_EXTENSION) {
+        match level_to_ascii(&template) {
+            Ok(text) => text.into_bytes(),
+            Err(error) => {
+                error!("Could not export {}: {}", level_asset_path, error);
+                return;
+            }
+        }
+    } else if level_asset_path.ends_with(VOX_EXTENSION) {
+        let vox_bytes = VoxPalette::read(None)
+            .map_err(|error| error.to_string())
+            .and_then(|palette| {
+                level_to_vox(&template, &palette).map_err(|error| error.to_string())
+            });
+
+        match vox_bytes {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                error!("Could not export {}: {}", level_asset_path, error);
+                return;
+            }
+        }
+    } else {
+        ron::ser::to_string_pretty(&template, PrettyConfig::default())
+            .unwrap()
+            .into_bytes()
+    };
 
     #[cfg(not(target_arch = "wasm32"))]
     IoTaskPool::get()
         .spawn(async move {
-            File::create(format!("assets/{}", level_asset_path))
-                .and_then(|mut file| file.write(ron_string.as_bytes()))
+            File::create(level_file_path(&level_asset_path))
+                .and_then(|mut file| file.write(&level_bytes))
                 .expect("Error while writing scene to file");
         })
         .detach();